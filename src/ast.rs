@@ -1,47 +1,104 @@
-use std::{any::Any, fmt};
-
-use crate::token::Token;
+use crate::token::{Span, Token};
 
 pub trait Node {
     fn token_literal(&self) -> String;
     fn string(&self) -> String;
 }
 
-pub trait Statement: Node {
-    fn statement_node(&self);
+#[derive(Debug, PartialEq, Clone)]
+pub enum Statement {
+    Let(LetStatement),
+    Return(ReturnStatement),
+    Expression(ExpressionStatement),
+    Error(ErrorStatement),
 }
 
-pub trait Expression: Node {
-    fn expression_node(&self);
-    fn as_any(&self) -> &dyn Any;
-}
+impl Node for Statement {
+    fn token_literal(&self) -> String {
+        match self {
+            Statement::Let(s) => s.token_literal(),
+            Statement::Return(s) => s.token_literal(),
+            Statement::Expression(s) => s.token_literal(),
+            Statement::Error(s) => s.token_literal(),
+        }
+    }
 
-impl fmt::Debug for dyn Statement {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> std::fmt::Result {
-        f.debug_struct("Statement").finish()
+    fn string(&self) -> String {
+        match self {
+            Statement::Let(s) => s.string(),
+            Statement::Return(s) => s.string(),
+            Statement::Expression(s) => s.string(),
+            Statement::Error(s) => s.string(),
+        }
     }
 }
 
-impl PartialEq<dyn Statement> for dyn Statement {
-    fn eq(&self, other: &dyn Statement) -> bool {
-        self.statement_node() == other.statement_node()
+#[derive(Debug, PartialEq, Clone)]
+pub enum Expression {
+    Identifier(Identifier),
+    IntegerLiteral(IntegerLiteral),
+    StringLiteral(StringLiteral),
+    Boolean(Boolean),
+    Prefix(PrefixExpression),
+    Infix(InfixExpression),
+    If(IfExpression),
+    Function(FunctionLiteral),
+    Call(CallExpression),
+    Array(ArrayLiteral),
+    Index(IndexExpression),
+    Hash(HashLiteral),
+}
+
+impl Node for Expression {
+    fn token_literal(&self) -> String {
+        match self {
+            Expression::Identifier(e) => e.token_literal(),
+            Expression::IntegerLiteral(e) => e.token_literal(),
+            Expression::StringLiteral(e) => e.token_literal(),
+            Expression::Boolean(e) => e.token_literal(),
+            Expression::Prefix(e) => e.token_literal(),
+            Expression::Infix(e) => e.token_literal(),
+            Expression::If(e) => e.token_literal(),
+            Expression::Function(e) => e.token_literal(),
+            Expression::Call(e) => e.token_literal(),
+            Expression::Array(e) => e.token_literal(),
+            Expression::Index(e) => e.token_literal(),
+            Expression::Hash(e) => e.token_literal(),
+        }
+    }
+
+    fn string(&self) -> String {
+        match self {
+            Expression::Identifier(e) => e.string(),
+            Expression::IntegerLiteral(e) => e.string(),
+            Expression::StringLiteral(e) => e.string(),
+            Expression::Boolean(e) => e.string(),
+            Expression::Prefix(e) => e.string(),
+            Expression::Infix(e) => e.string(),
+            Expression::If(e) => e.string(),
+            Expression::Function(e) => e.string(),
+            Expression::Call(e) => e.string(),
+            Expression::Array(e) => e.string(),
+            Expression::Index(e) => e.string(),
+            Expression::Hash(e) => e.string(),
+        }
     }
 }
-#[derive(Debug, PartialEq)]
+
+#[derive(Debug, PartialEq, Clone)]
 pub struct Program {
-    pub statements: Vec<Box<dyn Statement>>,
+    pub statements: Vec<Statement>,
 }
 
-impl Program {
-    fn token_literal(&mut self) -> String {
-        if self.statements.len() > 0 {
-            return self.statements[0].token_literal();
-        } else {
-            return String::from("");
+impl Node for Program {
+    fn token_literal(&self) -> String {
+        match self.statements.first() {
+            Some(stmt) => stmt.token_literal(),
+            None => String::from(""),
         }
     }
 
-    fn string(&mut self) -> String {
+    fn string(&self) -> String {
         let mut out = String::new();
         for stmt in self.statements.iter() {
             out.push_str(&stmt.string());
@@ -51,163 +108,435 @@ impl Program {
     }
 }
 
+#[derive(Debug, PartialEq, Clone)]
 pub struct LetStatement {
     pub token: Token, // the 'let' token.
     pub name: Identifier,
-    pub value: Box<dyn Expression>,
+    pub value: Expression,
 }
 
-impl LetStatement {}
-
 impl Node for LetStatement {
     fn token_literal(&self) -> String {
-        let l = self.token.literal.to_string();
-        l
+        self.token.literal.to_string()
     }
 
     fn string(&self) -> String {
         let mut out = String::new();
         out.push_str(&self.token_literal());
-        out.push_str(" ");
+        out.push(' ');
 
         out.push_str(&self.name.value);
         out.push_str(" = ");
 
-        match self.value.as_any().downcast_ref::<Identifier>() {
-            Some(val) => out.push_str(&val.string()),
-            _ => (),
-        };
+        out.push_str(&self.value.string());
 
-        out.push_str(";");
+        out.push(';');
 
         out
     }
 }
 
-impl Statement for LetStatement {
-    fn statement_node(&self) {}
-}
-
+#[derive(Debug, PartialEq, Clone)]
 pub struct ReturnStatement {
     pub token: Token, // the 'return' token;
-    pub return_value: Box<dyn Expression>,
-}
-
-// impl ReturnStatement {}
-
-impl Statement for ReturnStatement {
-    fn statement_node(&self) {}
+    pub return_value: Expression,
 }
 
 impl Node for ReturnStatement {
     fn token_literal(&self) -> String {
-        let l = self.token.literal.to_string();
-        l
+        self.token.literal.to_string()
     }
 
     fn string(&self) -> String {
         let mut out = String::new();
         out.push_str(&self.token_literal());
+        out.push(' ');
 
-        match self.return_value.as_any().downcast_ref::<Identifier>() {
-            Some(idt) => out.push_str(&idt.string()),
-            _ => (),
-        };
+        out.push_str(&self.return_value.string());
 
-        out.push_str(" ");
-        out.push_str(";");
+        out.push(';');
 
         out
     }
 }
 
+#[derive(Debug, PartialEq, Clone)]
 pub struct ExpressionStatement {
     pub token: Token, // the first token of the expression
-    pub expression: Box<dyn Expression>,
+    pub expression: Expression,
 }
 
-// impl ExpressionStatement {}
+impl Node for ExpressionStatement {
+    fn token_literal(&self) -> String {
+        self.token.literal.to_string()
+    }
 
-impl Statement for ExpressionStatement {
-    fn statement_node(&self) {}
+    fn string(&self) -> String {
+        self.expression.string()
+    }
 }
 
-impl Node for ExpressionStatement {
+/// Stands in for a statement the parser could not make sense of. `span`
+/// covers the source that was skipped while recovering, so tools can keep
+/// working on the rest of a broken program.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ErrorStatement {
+    pub token: Token, // the token the statement started at.
+    pub span: Span,
+}
+
+impl Node for ErrorStatement {
     fn token_literal(&self) -> String {
-        let l = self.token.literal.to_string();
-        l
+        self.token.literal.to_string()
     }
 
     fn string(&self) -> String {
-        let out = String::new();
+        String::from("<error>")
+    }
+}
 
-        match self
-            .expression
-            .as_any()
-            .downcast_ref::<Box<dyn Expression>>()
-        {
-            Some(e) => return e.string(),
-            _ => (),
-        };
+#[derive(Debug, PartialEq, Clone)]
+pub struct BlockStatement {
+    pub token: Token, // the '{' token.
+    pub statements: Vec<Statement>,
+}
+
+impl Node for BlockStatement {
+    fn token_literal(&self) -> String {
+        self.token.literal.to_string()
+    }
+
+    fn string(&self) -> String {
+        let mut out = String::new();
+        out.push('{');
+        for stmt in self.statements.iter() {
+            out.push(' ');
+            out.push_str(&stmt.string());
+            // Expression statements print without their terminator, so put
+            // one back to keep `{ a; (-b) }` from reading as a call.
+            if let Statement::Expression(_) = stmt {
+                out.push(';');
+            }
+        }
+        out.push_str(" }");
 
         out
     }
 }
 
+#[derive(Debug, PartialEq, Clone)]
 pub struct Identifier {
     pub token: Token, // the 'ident' token.
     pub value: String,
 }
 
-impl Identifier {}
-
 impl Node for Identifier {
     fn token_literal(&self) -> String {
-        let l = self.token.literal.to_string();
-        l
+        self.token.literal.to_string()
+    }
+
+    fn string(&self) -> String {
+        self.value.to_string()
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct IntegerLiteral {
+    pub token: Token, // the 'int' token.
+    pub value: i64,
+}
+
+impl Node for IntegerLiteral {
+    fn token_literal(&self) -> String {
+        self.token.literal.to_string()
     }
 
     fn string(&self) -> String {
-        let l = self.value.to_string();
-        l
+        self.token.literal.to_string()
     }
 }
 
-impl Expression for Identifier {
-    fn expression_node(&self) {}
-    fn as_any(&self) -> &dyn Any {
-        self
+#[derive(Debug, PartialEq, Clone)]
+pub struct StringLiteral {
+    pub token: Token, // the 'string' token.
+    pub value: String,
+}
+
+impl Node for StringLiteral {
+    fn token_literal(&self) -> String {
+        self.token.literal.to_string()
+    }
+
+    fn string(&self) -> String {
+        let mut out = String::new();
+        out.push('"');
+        for ch in self.value.chars() {
+            match ch {
+                '"' => out.push_str("\\\""),
+                '\\' => out.push_str("\\\\"),
+                '\n' => out.push_str("\\n"),
+                '\t' => out.push_str("\\t"),
+                c if c.is_control() => out.push_str(&format!("\\u{{{:x}}}", c as u32)),
+                c => out.push(c),
+            }
+        }
+        out.push('"');
+
+        out
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct PrefixExpression {
+    pub token: Token, // the prefix token, e.g. !
+    pub operator: String,
+    pub right: Box<Expression>,
+}
+
+impl Node for PrefixExpression {
+    fn token_literal(&self) -> String {
+        self.token.literal.to_string()
+    }
+
+    fn string(&self) -> String {
+        let mut out = String::new();
+        out.push('(');
+        out.push_str(&self.operator);
+        out.push_str(&self.right.string());
+        out.push(')');
+
+        out
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct InfixExpression {
+    pub token: Token, // the operator token, e.g. +
+    pub left: Box<Expression>,
+    pub operator: String,
+    pub right: Box<Expression>,
+}
+
+impl Node for InfixExpression {
+    fn token_literal(&self) -> String {
+        self.token.literal.to_string()
+    }
+
+    fn string(&self) -> String {
+        let mut out = String::new();
+        out.push('(');
+        out.push_str(&self.left.string());
+        out.push(' ');
+        out.push_str(&self.operator);
+        out.push(' ');
+        out.push_str(&self.right.string());
+        out.push(')');
+
+        out
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Boolean {
+    pub token: Token, // the 'true' or 'false' token.
+    pub value: bool,
+}
+
+impl Node for Boolean {
+    fn token_literal(&self) -> String {
+        self.token.literal.to_string()
+    }
+
+    fn string(&self) -> String {
+        self.token.literal.to_string()
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct IfExpression {
+    pub token: Token, // the 'if' token.
+    pub condition: Box<Expression>,
+    pub consequence: BlockStatement,
+    pub alternative: Option<BlockStatement>,
+}
+
+impl Node for IfExpression {
+    fn token_literal(&self) -> String {
+        self.token.literal.to_string()
+    }
+
+    fn string(&self) -> String {
+        let mut out = String::new();
+        out.push_str("if (");
+        out.push_str(&self.condition.string());
+        out.push_str(") ");
+        out.push_str(&self.consequence.string());
+
+        if let Some(alt) = &self.alternative {
+            out.push_str(" else ");
+            out.push_str(&alt.string());
+        }
+
+        out
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct FunctionLiteral {
+    pub token: Token, // the 'fn' token.
+    pub parameters: Vec<Identifier>,
+    pub body: BlockStatement,
+}
+
+impl Node for FunctionLiteral {
+    fn token_literal(&self) -> String {
+        self.token.literal.to_string()
+    }
+
+    fn string(&self) -> String {
+        let params: Vec<String> = self.parameters.iter().map(|p| p.string()).collect();
+
+        let mut out = String::new();
+        out.push_str(&self.token_literal());
+        out.push('(');
+        out.push_str(&params.join(", "));
+        out.push_str(") ");
+        out.push_str(&self.body.string());
+
+        out
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct CallExpression {
+    pub token: Token,              // the '(' token.
+    pub function: Box<Expression>, // Identifier or FunctionLiteral
+    pub arguments: Vec<Expression>,
+}
+
+impl Node for CallExpression {
+    fn token_literal(&self) -> String {
+        self.token.literal.to_string()
+    }
+
+    fn string(&self) -> String {
+        let args: Vec<String> = self.arguments.iter().map(|a| a.string()).collect();
+
+        let mut out = String::new();
+        out.push_str(&self.function.string());
+        out.push('(');
+        out.push_str(&args.join(", "));
+        out.push(')');
+
+        out
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct ArrayLiteral {
+    pub token: Token, // the '[' token.
+    pub elements: Vec<Expression>,
+}
+
+impl Node for ArrayLiteral {
+    fn token_literal(&self) -> String {
+        self.token.literal.to_string()
+    }
+
+    fn string(&self) -> String {
+        let elements: Vec<String> = self.elements.iter().map(|e| e.string()).collect();
+
+        let mut out = String::new();
+        out.push('[');
+        out.push_str(&elements.join(", "));
+        out.push(']');
+
+        out
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct IndexExpression {
+    pub token: Token, // the '[' token.
+    pub left: Box<Expression>,
+    pub index: Box<Expression>,
+}
+
+impl Node for IndexExpression {
+    fn token_literal(&self) -> String {
+        self.token.literal.to_string()
+    }
+
+    fn string(&self) -> String {
+        let mut out = String::new();
+        out.push('(');
+        out.push_str(&self.left.string());
+        out.push('[');
+        out.push_str(&self.index.string());
+        out.push_str("])");
+
+        out
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct HashLiteral {
+    pub token: Token, // the '{' token.
+    pub pairs: Vec<(Expression, Expression)>,
+}
+
+impl Node for HashLiteral {
+    fn token_literal(&self) -> String {
+        self.token.literal.to_string()
+    }
+
+    fn string(&self) -> String {
+        let pairs: Vec<String> = self
+            .pairs
+            .iter()
+            .map(|(key, value)| format!("{}: {}", key.string(), value.string()))
+            .collect();
+
+        let mut out = String::new();
+        out.push('{');
+        out.push_str(&pairs.join(", "));
+        out.push('}');
+
+        out
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::token::{Token, TokenKind};
+    use crate::token::{Span, Token, TokenKind};
 
-    use crate::ast::{Expression, Identifier, LetStatement, Program};
+    use crate::ast::{Expression, Identifier, LetStatement, Node, Program, Statement};
 
     #[test]
     fn test_string() {
-        let mut program = Program {
-            statements: vec![Box::new(LetStatement {
+        let program = Program {
+            statements: vec![Statement::Let(LetStatement {
                 token: Token {
                     token_kind: TokenKind::Let,
                     literal: String::from("let"),
+                    span: Span::default(),
                 },
                 name: Identifier {
                     token: Token {
                         token_kind: TokenKind::Ident,
                         literal: String::from("myVar"),
+                        span: Span::default(),
                     },
                     value: String::from("myVar"),
                 },
-                value: Box::new(Identifier {
+                value: Expression::Identifier(Identifier {
                     token: Token {
                         token_kind: TokenKind::Ident,
                         literal: String::from("anotherVar"),
+                        span: Span::default(),
                     },
                     value: String::from("anotherVar"),
-                }) as Box<dyn Expression>,
+                }),
             })],
         };
 
@@ -0,0 +1,118 @@
+use std::{collections::BTreeMap, rc::Rc};
+
+use crate::object::{Arity, Builtin, Object, NULL};
+
+/// The native functions visible to Monkey programs. Hosts embedding the
+/// interpreter can add their own with [`Builtins::register`].
+#[derive(Default)]
+pub struct Builtins {
+    functions: BTreeMap<String, Rc<Builtin>>,
+}
+
+impl Builtins {
+    /// Creates an empty registry.
+    pub fn new() -> Builtins {
+        Builtins {
+            functions: BTreeMap::new(),
+        }
+    }
+
+    /// Creates a registry holding the standard builtins.
+    pub fn standard() -> Builtins {
+        let mut builtins = Builtins::new();
+        builtins.register("len", Arity::Exact(1), len);
+        builtins.register("first", Arity::Exact(1), first);
+        builtins.register("last", Arity::Exact(1), last);
+        builtins.register("rest", Arity::Exact(1), rest);
+        builtins.register("push", Arity::Exact(2), push);
+        builtins.register("puts", Arity::Variadic, puts);
+        builtins
+    }
+
+    /// Registers `func` under `name`, replacing any builtin already
+    /// registered with that name. The argument count is checked against
+    /// `arity` before `func` is called.
+    pub fn register<F>(&mut self, name: &str, arity: Arity, func: F)
+    where
+        F: Fn(&[Object]) -> Object + 'static,
+    {
+        let builtin = Builtin {
+            name: name.to_string(),
+            arity,
+            func: Box::new(func),
+        };
+        self.functions.insert(name.to_string(), Rc::new(builtin));
+    }
+
+    pub fn lookup(&self, name: &str) -> Option<Object> {
+        self.functions.get(name).cloned().map(Object::Builtin)
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.functions.keys().map(|name| name.as_str())
+    }
+}
+
+fn len(args: &[Object]) -> Object {
+    match &args[0] {
+        Object::String(value) => Object::Integer(value.chars().count() as i64),
+        Object::Array(elements) => Object::Integer(elements.len() as i64),
+        arg => Object::Error(format!(
+            "argument to `len` not supported, got {}",
+            arg.type_name()
+        )),
+    }
+}
+
+fn first(args: &[Object]) -> Object {
+    match &args[0] {
+        Object::Array(elements) => elements.first().cloned().unwrap_or(NULL),
+        arg => Object::Error(format!(
+            "argument to `first` must be ARRAY, got {}",
+            arg.type_name()
+        )),
+    }
+}
+
+fn last(args: &[Object]) -> Object {
+    match &args[0] {
+        Object::Array(elements) => elements.last().cloned().unwrap_or(NULL),
+        arg => Object::Error(format!(
+            "argument to `last` must be ARRAY, got {}",
+            arg.type_name()
+        )),
+    }
+}
+
+fn rest(args: &[Object]) -> Object {
+    match &args[0] {
+        Object::Array(elements) if elements.is_empty() => NULL,
+        Object::Array(elements) => Object::Array(elements[1..].to_vec()),
+        arg => Object::Error(format!(
+            "argument to `rest` must be ARRAY, got {}",
+            arg.type_name()
+        )),
+    }
+}
+
+fn push(args: &[Object]) -> Object {
+    match &args[0] {
+        Object::Array(elements) => {
+            let mut new_elements = elements.clone();
+            new_elements.push(args[1].clone());
+            Object::Array(new_elements)
+        }
+        arg => Object::Error(format!(
+            "argument to `push` must be ARRAY, got {}",
+            arg.type_name()
+        )),
+    }
+}
+
+fn puts(args: &[Object]) -> Object {
+    for arg in args.iter() {
+        println!("{}", arg.inspect());
+    }
+
+    NULL
+}
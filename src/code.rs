@@ -0,0 +1,356 @@
+//! Bytecode: the opcodes the [compiler](crate::compiler) emits and the
+//! [vm](crate::vm) executes, and how their operands are encoded.
+//!
+//! An instruction is one opcode byte followed by its operands, each a
+//! big-endian unsigned integer of the width given by the opcode's
+//! [`Definition`].
+
+use std::{
+    fmt,
+    ops::{Deref, DerefMut},
+};
+
+/// A sequence of encoded instructions.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct Instructions(pub Vec<u8>);
+
+impl Deref for Instructions {
+    type Target = Vec<u8>;
+
+    fn deref(&self) -> &Vec<u8> {
+        &self.0
+    }
+}
+
+impl DerefMut for Instructions {
+    fn deref_mut(&mut self) -> &mut Vec<u8> {
+        &mut self.0
+    }
+}
+
+impl Instructions {
+    /// Decodes the instruction at `offset`, failing on an unknown opcode or
+    /// an instruction cut short by the end of the code.
+    pub fn instruction_at(&self, offset: usize) -> Result<(Opcode, Vec<usize>), String> {
+        let byte = self[offset];
+        let op = Opcode::from_byte(byte).ok_or_else(|| format!("unknown opcode {}", byte))?;
+        let def = op.definition();
+        if offset + op.width() > self.len() {
+            return Err(format!("truncated {}", def.name));
+        }
+        let (operands, _) = read_operands(&def, &self[offset + 1..]);
+        Ok((op, operands))
+    }
+}
+
+/// Lists the instructions one per line, with their offsets, opcode names
+/// and operands. Undecodable bytes end the listing with an error, as
+/// nothing after them can be trusted.
+impl fmt::Display for Instructions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut offset = 0;
+        while offset < self.len() {
+            match self.instruction_at(offset) {
+                Ok((op, operands)) => {
+                    writeln!(f, "{:04} {}", offset, format_instruction(op, &operands))?;
+                    offset += op.width();
+                }
+                Err(err) => return writeln!(f, "{:04} ERROR: {}", offset, err),
+            }
+        }
+        Ok(())
+    }
+}
+
+impl From<Vec<u8>> for Instructions {
+    fn from(bytes: Vec<u8>) -> Instructions {
+        Instructions(bytes)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[repr(u8)]
+pub enum Opcode {
+    /// Pushes the constant at the operand's index in the constant pool.
+    Constant,
+    /// Discards the top of the stack.
+    Pop,
+
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Equal,
+    NotEqual,
+    GreaterThan,
+    LessThan,
+    Minus,
+    Bang,
+
+    True,
+    False,
+    Null,
+
+    /// Jumps to the operand's offset when the popped value is not truthy.
+    JumpNotTruthy,
+    /// Jumps to the operand's offset.
+    Jump,
+
+    /// Pushes the global in the operand's slot, falling back to the
+    /// builtin of that name while the global is unset.
+    GetGlobal,
+    /// Pops a value into the operand's global slot.
+    SetGlobal,
+    /// Pushes the current function's local in the operand's slot.
+    GetLocal,
+    /// Pops a value into the current function's local slot.
+    SetLocal,
+    /// Pushes a local of an enclosing function: the first operand counts
+    /// functions outwards, the second is the slot.
+    GetOuter,
+
+    /// Builds an array from the operand's number of values.
+    Array,
+    /// Builds a hash from the operand's number of key-value pairs.
+    Hash,
+    /// Fails unless the top of the stack can be used as a hash key.
+    HashKey,
+    /// Pops an index and the value it indexes.
+    Index,
+
+    /// Calls the function below the operand's number of arguments.
+    Call,
+    /// Returns the popped value from the current function.
+    ReturnValue,
+    /// Pushes a closure over the compiled function at the operand's index
+    /// in the constant pool.
+    Closure,
+}
+
+/// The name and operand widths of an opcode.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Definition {
+    pub name: &'static str,
+    pub operand_widths: &'static [usize],
+}
+
+static OPCODES: [Opcode; 29] = [
+    Opcode::Constant,
+    Opcode::Pop,
+    Opcode::Add,
+    Opcode::Sub,
+    Opcode::Mul,
+    Opcode::Div,
+    Opcode::Equal,
+    Opcode::NotEqual,
+    Opcode::GreaterThan,
+    Opcode::LessThan,
+    Opcode::Minus,
+    Opcode::Bang,
+    Opcode::True,
+    Opcode::False,
+    Opcode::Null,
+    Opcode::JumpNotTruthy,
+    Opcode::Jump,
+    Opcode::GetGlobal,
+    Opcode::SetGlobal,
+    Opcode::GetLocal,
+    Opcode::SetLocal,
+    Opcode::GetOuter,
+    Opcode::Array,
+    Opcode::Hash,
+    Opcode::HashKey,
+    Opcode::Index,
+    Opcode::Call,
+    Opcode::ReturnValue,
+    Opcode::Closure,
+];
+
+impl Opcode {
+    /// Decodes an opcode byte, or returns `None` for a byte that is not
+    /// one.
+    pub fn from_byte(byte: u8) -> Option<Opcode> {
+        OPCODES.get(byte as usize).copied()
+    }
+
+    pub fn definition(self) -> Definition {
+        let (name, operand_widths): (&'static str, &'static [usize]) = match self {
+            Opcode::Constant => ("OpConstant", &[2]),
+            Opcode::Pop => ("OpPop", &[]),
+            Opcode::Add => ("OpAdd", &[]),
+            Opcode::Sub => ("OpSub", &[]),
+            Opcode::Mul => ("OpMul", &[]),
+            Opcode::Div => ("OpDiv", &[]),
+            Opcode::Equal => ("OpEqual", &[]),
+            Opcode::NotEqual => ("OpNotEqual", &[]),
+            Opcode::GreaterThan => ("OpGreaterThan", &[]),
+            Opcode::LessThan => ("OpLessThan", &[]),
+            Opcode::Minus => ("OpMinus", &[]),
+            Opcode::Bang => ("OpBang", &[]),
+            Opcode::True => ("OpTrue", &[]),
+            Opcode::False => ("OpFalse", &[]),
+            Opcode::Null => ("OpNull", &[]),
+            Opcode::JumpNotTruthy => ("OpJumpNotTruthy", &[2]),
+            Opcode::Jump => ("OpJump", &[2]),
+            Opcode::GetGlobal => ("OpGetGlobal", &[2]),
+            Opcode::SetGlobal => ("OpSetGlobal", &[2]),
+            Opcode::GetLocal => ("OpGetLocal", &[2]),
+            Opcode::SetLocal => ("OpSetLocal", &[2]),
+            Opcode::GetOuter => ("OpGetOuter", &[1, 2]),
+            Opcode::Array => ("OpArray", &[2]),
+            Opcode::Hash => ("OpHash", &[2]),
+            Opcode::HashKey => ("OpHashKey", &[]),
+            Opcode::Index => ("OpIndex", &[]),
+            Opcode::Call => ("OpCall", &[1]),
+            Opcode::ReturnValue => ("OpReturnValue", &[]),
+            Opcode::Closure => ("OpClosure", &[2]),
+        };
+        Definition {
+            name,
+            operand_widths,
+        }
+    }
+
+    /// Returns the encoded length of an instruction with this opcode.
+    pub fn width(self) -> usize {
+        1 + self.definition().operand_widths.iter().sum::<usize>()
+    }
+}
+
+/// Encodes an instruction. Operands too large for their width are
+/// truncated; the compiler checks its limits before calling this.
+pub fn make(op: Opcode, operands: &[usize]) -> Vec<u8> {
+    let def = op.definition();
+    let mut instruction = Vec::with_capacity(op.width());
+    instruction.push(op as u8);
+
+    for (operand, width) in operands.iter().zip(def.operand_widths) {
+        match width {
+            2 => instruction.extend_from_slice(&(*operand as u16).to_be_bytes()),
+            1 => instruction.push(*operand as u8),
+            _ => unreachable!("operand width {}", width),
+        }
+    }
+
+    instruction
+}
+
+/// Decodes the operands that follow an opcode, returning them and how
+/// many bytes they took.
+pub fn read_operands(def: &Definition, ins: &[u8]) -> (Vec<usize>, usize) {
+    let mut operands = Vec::with_capacity(def.operand_widths.len());
+    let mut offset = 0;
+
+    for width in def.operand_widths {
+        match width {
+            2 => operands.push(read_u16(&ins[offset..]) as usize),
+            1 => operands.push(ins[offset] as usize),
+            _ => unreachable!("operand width {}", width),
+        }
+        offset += width;
+    }
+
+    (operands, offset)
+}
+
+pub fn read_u16(ins: &[u8]) -> u16 {
+    u16::from_be_bytes([ins[0], ins[1]])
+}
+
+/// Formats an instruction as its opcode name followed by its operands.
+pub fn format_instruction(op: Opcode, operands: &[usize]) -> String {
+    let mut out = String::from(op.definition().name);
+    for operand in operands {
+        out.push_str(&format!(" {}", operand));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::code::{make, read_operands, Instructions, Opcode, OPCODES};
+
+    #[test]
+    fn test_make() {
+        let tests = vec![
+            (
+                Opcode::Constant,
+                vec![65534],
+                vec![Opcode::Constant as u8, 255, 254],
+            ),
+            (Opcode::Add, vec![], vec![Opcode::Add as u8]),
+            (Opcode::Call, vec![255], vec![Opcode::Call as u8, 255]),
+            (
+                Opcode::GetOuter,
+                vec![2, 258],
+                vec![Opcode::GetOuter as u8, 2, 1, 2],
+            ),
+        ];
+
+        for (op, operands, expected) in tests {
+            assert_eq!(make(op, &operands), expected, "{:?}", op);
+        }
+    }
+
+    #[test]
+    fn test_read_operands() {
+        let tests = vec![
+            (Opcode::Constant, vec![65535], 2),
+            (Opcode::Call, vec![255], 1),
+            (Opcode::GetOuter, vec![3, 65535], 3),
+            (Opcode::Pop, vec![], 0),
+        ];
+
+        for (op, operands, bytes_read) in tests {
+            let instruction = make(op, &operands);
+            let (read, n) = read_operands(&op.definition(), &instruction[1..]);
+            assert_eq!(n, bytes_read, "{:?}", op);
+            assert_eq!(read, operands, "{:?}", op);
+        }
+    }
+
+    #[test]
+    fn test_opcode_bytes() {
+        for (byte, op) in OPCODES.iter().enumerate() {
+            assert_eq!(*op as u8 as usize, byte);
+            assert_eq!(Opcode::from_byte(byte as u8), Some(*op));
+        }
+        assert_eq!(Opcode::from_byte(OPCODES.len() as u8), None);
+    }
+
+    #[test]
+    fn test_instructions_string() {
+        let instructions = Instructions(
+            [
+                make(Opcode::Add, &[]),
+                make(Opcode::GetLocal, &[1]),
+                make(Opcode::Constant, &[2]),
+                make(Opcode::GetOuter, &[1, 65535]),
+                make(Opcode::Call, &[2]),
+            ]
+            .concat(),
+        );
+        let expected = "\
+0000 OpAdd
+0001 OpGetLocal 1
+0004 OpConstant 2
+0007 OpGetOuter 1 65535
+0011 OpCall 2
+";
+        assert_eq!(instructions.to_string(), expected);
+
+        let tests = vec![
+            (
+                vec![Opcode::Pop as u8, 200],
+                "0000 OpPop\n0001 ERROR: unknown opcode 200\n",
+            ),
+            (
+                vec![Opcode::Jump as u8, 0],
+                "0000 ERROR: truncated OpJump\n",
+            ),
+        ];
+        for (bytes, expected) in tests {
+            assert_eq!(Instructions(bytes).to_string(), expected);
+        }
+    }
+}
@@ -0,0 +1,452 @@
+//! The subcommands of the `monkey` binary. Each takes the arguments after
+//! its name and returns the process exit status.
+
+use std::{
+    fs,
+    io::{self, IsTerminal, Read, Write},
+    path::Path,
+};
+
+use monkey_rs::{
+    ast::Node,
+    compiler::{Bytecode, Compiler},
+    diagnostic::{Diagnostic, Renderer},
+    disasm::Disassembler,
+    formatter::Formatter,
+    lexer::Lexer,
+    mkc,
+    parser::Parser,
+    token::{Token, TokenKind},
+    vm::Vm,
+    Error, Interpreter,
+};
+
+// Exit statuses, following sysexits(3).
+pub const EXIT_USAGE: i32 = 64;
+pub const EXIT_SYNTAX: i32 = 65;
+pub const EXIT_RUNTIME: i32 = 70;
+pub const EXIT_IO: i32 = 74;
+
+/// `monkey run`: evaluates a script, or compiles it to bytecode and runs
+/// that with `--vm`. Syntax errors are reported before anything runs. A
+/// `.mkc` file from `monkey compile` runs on the VM as it is.
+pub fn run(args: &[String]) -> i32 {
+    let (vm, args) = match args {
+        [flag, rest @ ..] if flag == "--vm" => (true, rest),
+        _ => (false, args),
+    };
+    let path = match single_path("run", "[--vm] FILE", args) {
+        Ok(path) => path,
+        Err(status) => return status,
+    };
+    let (name, bytes) = match read_file(path) {
+        Ok(read) => read,
+        Err(err) => {
+            eprintln!("monkey run: {}: {}", path, err);
+            return EXIT_IO;
+        }
+    };
+
+    if path.ends_with(".mkc") || mkc::is_mkc(&bytes) {
+        let bytecode = match mkc::decode(&bytes) {
+            Ok(bytecode) => bytecode,
+            Err(err) => {
+                eprintln!("monkey run: {}: invalid bytecode: {}", path, err);
+                return EXIT_SYNTAX;
+            }
+        };
+        return match Vm::new().run(&bytecode) {
+            Ok(_) => 0,
+            Err(message) => {
+                eprintln!("{}: runtime error: {}", name, message);
+                EXIT_RUNTIME
+            }
+        };
+    }
+
+    let source = match into_source(bytes) {
+        Ok(source) => source,
+        Err(err) => {
+            eprintln!("monkey run: {}: {}", path, err);
+            return EXIT_IO;
+        }
+    };
+    let result = if vm {
+        compile_and_run(&source)
+    } else {
+        Interpreter::new().eval_str(&source).map(|_| ())
+    };
+    match result {
+        Ok(()) => 0,
+        Err(Error::Parse(errors)) => {
+            print_diagnostics(&name, &source, &errors);
+            EXIT_SYNTAX
+        }
+        Err(Error::Runtime(message)) => {
+            eprintln!("{}: runtime error: {}", name, message);
+            EXIT_RUNTIME
+        }
+    }
+}
+
+fn compile_and_run(source: &str) -> Result<(), Error> {
+    let bytecode = compile_source(source).map_err(Error::Parse)?;
+    Vm::new().run(&bytecode).map(|_| ()).map_err(Error::Runtime)
+}
+
+fn compile_source(source: &str) -> Result<Bytecode, Vec<Diagnostic>> {
+    let mut p = Parser::new(Lexer::new(source.to_string()));
+    let program = p.parse_program();
+    if !p.errors.is_empty() {
+        return Err(p.errors);
+    }
+    Compiler::new().compile(&program)
+}
+
+const COMPILE_USAGE: &str = "usage: monkey compile FILE [-o OUT]";
+
+/// `monkey compile`: compiles a script to a `.mkc` file for `monkey run`.
+/// The output goes next to the script unless `-o` names it; `-o -` writes
+/// to standard output.
+pub fn compile(args: &[String]) -> i32 {
+    let mut path = None;
+    let mut output = None;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-o" => match args.next() {
+                Some(out) => output = Some(out.clone()),
+                None => {
+                    eprintln!("monkey compile: -o expects a file name");
+                    return EXIT_USAGE;
+                }
+            },
+            "-h" | "--help" => {
+                println!("{}", COMPILE_USAGE);
+                return 0;
+            }
+            flag if flag.starts_with('-') && flag != "-" => {
+                eprintln!("monkey compile: unknown option {}\n{}", flag, COMPILE_USAGE);
+                return EXIT_USAGE;
+            }
+            arg if path.is_none() => path = Some(arg),
+            _ => {
+                eprintln!("{}", COMPILE_USAGE);
+                return EXIT_USAGE;
+            }
+        }
+    }
+    let (path, output) = match (path, output) {
+        (Some(path), Some(output)) => (path, output),
+        (Some(path), None) if path != "-" => {
+            let output = Path::new(path).with_extension("mkc");
+            (path, output.to_string_lossy().into_owned())
+        }
+        _ => {
+            eprintln!("{}", COMPILE_USAGE);
+            return EXIT_USAGE;
+        }
+    };
+
+    let (name, source) = match read_source(path) {
+        Ok(read) => read,
+        Err(err) => {
+            eprintln!("monkey compile: {}: {}", path, err);
+            return EXIT_IO;
+        }
+    };
+    let bytecode = match compile_source(&source) {
+        Ok(bytecode) => bytecode,
+        Err(errors) => {
+            print_diagnostics(&name, &source, &errors);
+            return EXIT_SYNTAX;
+        }
+    };
+
+    let bytes = mkc::encode(&bytecode);
+    let written = if output == "-" {
+        io::stdout().write_all(&bytes)
+    } else {
+        fs::write(&output, bytes)
+    };
+    match written {
+        Ok(()) => 0,
+        Err(err) => {
+            eprintln!("monkey compile: {}: {}", output, err);
+            EXIT_IO
+        }
+    }
+}
+
+/// `monkey lex`: prints a script's tokens, one per line with its position.
+pub fn lex(args: &[String]) -> i32 {
+    let (name, source) = match single_source("lex", "FILE", args) {
+        Ok(read) => read,
+        Err(status) => return status,
+    };
+
+    let mut lexer = Lexer::new(source.clone());
+    let mut errors = vec![];
+    loop {
+        let tok = lexer.next_token();
+        if tok.token_kind == TokenKind::Eof {
+            break;
+        }
+        println!("{}", token_line(&tok));
+        if tok.token_kind == TokenKind::Illegal {
+            errors.push(Diagnostic::error("E0004", tok.literal, tok.span));
+        }
+    }
+
+    if errors.is_empty() {
+        0
+    } else {
+        print_diagnostics(&name, &source, &errors);
+        EXIT_SYNTAX
+    }
+}
+
+/// Formats a token the way `monkey lex` prints it: position, kind and
+/// literal, in aligned columns.
+pub fn token_line(tok: &Token) -> String {
+    format!(
+        "{:<8} {:<10} {:?}",
+        tok.span.to_string(),
+        format!("{:?}", tok.token_kind),
+        tok.literal
+    )
+}
+
+/// `monkey parse`: prints each statement of a script as the parser
+/// understood it, fully parenthesized.
+pub fn parse(args: &[String]) -> i32 {
+    let (name, source) = match single_source("parse", "FILE", args) {
+        Ok(read) => read,
+        Err(status) => return status,
+    };
+
+    let mut p = Parser::new(Lexer::new(source.clone()));
+    let program = p.parse_program();
+    if !p.errors.is_empty() {
+        print_diagnostics(&name, &source, &p.errors);
+        return EXIT_SYNTAX;
+    }
+
+    for stmt in program.statements.iter() {
+        println!("{}", stmt.string());
+    }
+    0
+}
+
+/// `monkey disasm`: compiles a script and prints its bytecode, annotated
+/// with the source lines it came from.
+pub fn disasm(args: &[String]) -> i32 {
+    let (name, source) = match single_source("disasm", "FILE", args) {
+        Ok(read) => read,
+        Err(status) => return status,
+    };
+
+    match compile_source(&source) {
+        Ok(bytecode) => {
+            print!(
+                "{}",
+                Disassembler::new(&bytecode)
+                    .with_source(&source)
+                    .disassemble()
+            );
+            0
+        }
+        Err(errors) => {
+            print_diagnostics(&name, &source, &errors);
+            EXIT_SYNTAX
+        }
+    }
+}
+
+/// `monkey check`: reports the syntax errors in each script without
+/// running any of them.
+pub fn check(args: &[String]) -> i32 {
+    let mut paths: Vec<&str> = vec![];
+    for arg in args.iter() {
+        match arg.as_str() {
+            "-h" | "--help" => {
+                println!("usage: monkey check FILE...");
+                return 0;
+            }
+            flag if flag.starts_with('-') && flag != "-" => {
+                eprintln!("monkey check: unknown option {}", flag);
+                return EXIT_USAGE;
+            }
+            path => paths.push(path),
+        }
+    }
+    if paths.is_empty() {
+        eprintln!("usage: monkey check FILE...");
+        return EXIT_USAGE;
+    }
+
+    let mut status = 0;
+    for path in paths {
+        let (name, source) = match read_source(path) {
+            Ok(read) => read,
+            Err(err) => {
+                eprintln!("monkey check: {}: {}", path, err);
+                status = EXIT_IO;
+                continue;
+            }
+        };
+
+        let mut p = Parser::new(Lexer::new(source.clone()));
+        p.parse_program();
+        if !p.errors.is_empty() {
+            print_diagnostics(&name, &source, &p.errors);
+            status = status.max(EXIT_SYNTAX);
+        }
+    }
+
+    status
+}
+
+// Reads the one script `command` takes, or returns the status to exit with.
+fn single_source(command: &str, synopsis: &str, args: &[String]) -> Result<(String, String), i32> {
+    let path = single_path(command, synopsis, args)?;
+    read_source(path).map_err(|err| {
+        eprintln!("monkey {}: {}: {}", command, path, err);
+        EXIT_IO
+    })
+}
+
+// Returns the one path `command` takes, or the status to exit with.
+fn single_path<'a>(command: &str, synopsis: &str, args: &'a [String]) -> Result<&'a str, i32> {
+    let usage = format!("usage: monkey {} {}", command, synopsis);
+    match args {
+        [arg] if arg == "-h" || arg == "--help" => {
+            println!("{}", usage);
+            Err(0)
+        }
+        [path] if path == "-" || !path.starts_with('-') => Ok(path),
+        _ => {
+            eprintln!("{}", usage);
+            Err(EXIT_USAGE)
+        }
+    }
+}
+
+const FMT_USAGE: &str = "usage: monkey fmt [--check] [--width N] [--indent N] [FILE|-]...";
+
+/// `monkey fmt`: rewrites each file in the canonical style, or checks that
+/// it already is one with `--check`. Standard input, named `-`, is written
+/// to standard output.
+pub fn fmt(args: &[String]) -> i32 {
+    let mut check = false;
+    let mut formatter = Formatter::new();
+    let mut paths = vec![];
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--check" => check = true,
+            "--width" | "--indent" => {
+                let value = match args.next().and_then(|v| v.parse::<usize>().ok()) {
+                    Some(value) if value > 0 => value,
+                    _ => {
+                        eprintln!("monkey fmt: {} expects a positive number", arg);
+                        return EXIT_USAGE;
+                    }
+                };
+                formatter = if arg == "--width" {
+                    formatter.with_width(value)
+                } else {
+                    formatter.with_indent(value)
+                };
+            }
+            "-h" | "--help" => {
+                println!("{}", FMT_USAGE);
+                return 0;
+            }
+            flag if flag.starts_with('-') && flag != "-" => {
+                eprintln!("monkey fmt: unknown option {}\n{}", flag, FMT_USAGE);
+                return EXIT_USAGE;
+            }
+            path => paths.push(path),
+        }
+    }
+    if paths.is_empty() {
+        paths.push("-");
+    }
+
+    let mut status = 0;
+    for path in paths {
+        let (name, source) = match read_source(path) {
+            Ok(read) => read,
+            Err(err) => {
+                eprintln!("monkey fmt: {}: {}", path, err);
+                status = EXIT_IO;
+                continue;
+            }
+        };
+
+        let formatted = match formatter.format(&source) {
+            Ok(formatted) => formatted,
+            Err(errors) => {
+                print_diagnostics(&name, &source, &errors);
+                status = status.max(EXIT_SYNTAX);
+                continue;
+            }
+        };
+
+        if check {
+            if formatted != source {
+                println!("would reformat {}", name);
+                status = status.max(1);
+            }
+        } else if path == "-" {
+            print!("{}", formatted);
+        } else if formatted != source {
+            if let Err(err) = fs::write(path, formatted) {
+                eprintln!("monkey fmt: {}: {}", path, err);
+                status = EXIT_IO;
+            }
+        }
+    }
+
+    status
+}
+
+/// Reads the file at `path`, or standard input for `-`, returning the name
+/// to report it under along with its contents.
+pub fn read_source(path: &str) -> io::Result<(String, String)> {
+    let (name, bytes) = read_file(path)?;
+    Ok((name, into_source(bytes)?))
+}
+
+fn read_file(path: &str) -> io::Result<(String, Vec<u8>)> {
+    if path == "-" {
+        let mut bytes = vec![];
+        io::stdin().read_to_end(&mut bytes)?;
+        Ok((String::from("<stdin>"), bytes))
+    } else {
+        Ok((path.to_string(), fs::read(path)?))
+    }
+}
+
+fn into_source(bytes: Vec<u8>) -> io::Result<String> {
+    String::from_utf8(bytes).map_err(|_| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            "stream did not contain valid UTF-8",
+        )
+    })
+}
+
+/// Renders diagnostics to standard error, in color when it is a terminal
+/// and `NO_COLOR` is unset.
+pub fn print_diagnostics(name: &str, source: &str, diagnostics: &[Diagnostic]) {
+    let color = io::stderr().is_terminal() && std::env::var_os("NO_COLOR").is_none();
+    let renderer = Renderer::new(name, source).with_color(color);
+
+    for diagnostic in diagnostics.iter() {
+        eprint!("{}", renderer.render(diagnostic));
+    }
+}
@@ -0,0 +1,732 @@
+//! Lowers a [`Program`] to [`Bytecode`] for the [vm](crate::vm).
+//!
+//! Variables are resolved to slots at compile time, but with the
+//! evaluator's semantics: every `let` or parameter of a function gets a
+//! slot in that function's locals, closures share the locals of the call
+//! that created them instead of copying them, and globals are looked up
+//! when the code runs, so a function may use a global defined after it.
+
+use std::rc::Rc;
+
+use crate::{
+    ast::{
+        BlockStatement, Expression, FunctionLiteral, HashLiteral, Identifier, IfExpression, Node,
+        Program, Statement,
+    },
+    code::{make, Instructions, Opcode},
+    diagnostic::Diagnostic,
+    object::{CompiledFunction, Object},
+    token::Span,
+};
+
+/// A compiled program: the top-level code as a function without
+/// parameters, the constant pool, and the name of each global slot.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Bytecode {
+    pub main: Rc<CompiledFunction>,
+    pub constants: Vec<Object>,
+    pub globals: Vec<String>,
+}
+
+/// Compiles programs one after another, like the lines of a REPL session:
+/// constants and global slots carry over, so the [`Bytecode`] of each
+/// program extends that of the ones before.
+#[derive(Default)]
+pub struct Compiler {
+    constants: Vec<Object>,
+    globals: Vec<String>,
+    // The functions being compiled, innermost last. The first is the top
+    // level, which has no locals.
+    scopes: Vec<CompilationScope>,
+}
+
+#[derive(Default)]
+struct CompilationScope {
+    instructions: Instructions,
+    locals: Vec<String>,
+    lines: Vec<(usize, usize)>,
+    // The line of the statement being compiled.
+    line: usize,
+}
+
+impl Compiler {
+    pub fn new() -> Compiler {
+        Compiler {
+            constants: vec![],
+            globals: vec![],
+            scopes: vec![],
+        }
+    }
+
+    /// Compiles `program`. Fails on syntax errors left in the program and
+    /// on programs too large for the bytecode's operands.
+    pub fn compile(&mut self, program: &Program) -> Result<Bytecode, Vec<Diagnostic>> {
+        self.scopes = vec![CompilationScope::default()];
+        self.compile_body(&program.statements)
+            .map_err(|err| vec![*err])?;
+        self.emit(Opcode::ReturnValue, &[]);
+
+        let scope = self.scopes.pop().unwrap();
+        let main = CompiledFunction {
+            instructions: scope.instructions,
+            num_parameters: 0,
+            locals: vec![],
+            source: String::new(),
+            lines: scope.lines,
+        };
+        Ok(Bytecode {
+            main: Rc::new(main),
+            constants: self.constants.clone(),
+            globals: self.globals.clone(),
+        })
+    }
+
+    /// Compiles statements so that they leave the value of the last one on
+    /// the stack, as the evaluator returns it: null unless it is an
+    /// expression.
+    fn compile_body(&mut self, statements: &[Statement]) -> Result<(), Box<Diagnostic>> {
+        let (last, rest) = match statements.split_last() {
+            Some(split) => split,
+            None => {
+                self.emit(Opcode::Null, &[]);
+                return Ok(());
+            }
+        };
+
+        // Whatever follows the body, like the jump over an `else`, belongs
+        // to the statement around it.
+        let outer = self.scope().line;
+        for stmt in rest.iter() {
+            self.set_line(stmt);
+            self.compile_statement(stmt)?;
+        }
+        self.set_line(last);
+        match last {
+            Statement::Expression(s) => self.compile_expression(&s.expression)?,
+            // Nothing after a return runs.
+            Statement::Return(_) => self.compile_statement(last)?,
+            _ => {
+                self.compile_statement(last)?;
+                self.emit(Opcode::Null, &[]);
+            }
+        }
+        self.scope().line = outer;
+        Ok(())
+    }
+
+    fn set_line(&mut self, stmt: &Statement) {
+        let span = match stmt {
+            Statement::Let(s) => s.token.span,
+            Statement::Return(s) => s.token.span,
+            Statement::Expression(s) => s.token.span,
+            Statement::Error(s) => s.span,
+        };
+        self.scope().line = span.line;
+    }
+
+    fn compile_statement(&mut self, stmt: &Statement) -> Result<(), Box<Diagnostic>> {
+        match stmt {
+            Statement::Expression(s) => {
+                self.compile_expression(&s.expression)?;
+                self.emit(Opcode::Pop, &[]);
+            }
+            Statement::Let(s) => {
+                self.compile_expression(&s.value)?;
+                match self.local_slot(&s.name.value) {
+                    Some((0, slot)) => self.emit(Opcode::SetLocal, &[slot]),
+                    _ => {
+                        let slot = self.global_slot(&s.name)?;
+                        self.emit(Opcode::SetGlobal, &[slot])
+                    }
+                };
+            }
+            Statement::Return(s) => {
+                self.compile_expression(&s.return_value)?;
+                self.emit(Opcode::ReturnValue, &[]);
+            }
+            Statement::Error(s) => {
+                return Err(Box::new(Diagnostic::error(
+                    "E0006",
+                    String::from("cannot compile a program with syntax errors"),
+                    s.span,
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    fn compile_expression(&mut self, exp: &Expression) -> Result<(), Box<Diagnostic>> {
+        match exp {
+            Expression::IntegerLiteral(e) => {
+                let index = self.add_constant(Object::Integer(e.value), e.token.span)?;
+                self.emit(Opcode::Constant, &[index]);
+            }
+            Expression::StringLiteral(e) => {
+                let index = self.add_constant(Object::String(e.value.clone()), e.token.span)?;
+                self.emit(Opcode::Constant, &[index]);
+            }
+            Expression::Boolean(e) => {
+                let op = if e.value { Opcode::True } else { Opcode::False };
+                self.emit(op, &[]);
+            }
+            Expression::Identifier(e) => self.compile_identifier(e)?,
+            Expression::Prefix(e) => {
+                self.compile_expression(&e.right)?;
+                let op = match e.operator.as_str() {
+                    "!" => Opcode::Bang,
+                    "-" => Opcode::Minus,
+                    op => return Err(unknown_operator(op, e.token.span)),
+                };
+                self.emit(op, &[]);
+            }
+            Expression::Infix(e) => {
+                self.compile_expression(&e.left)?;
+                self.compile_expression(&e.right)?;
+                let op = match e.operator.as_str() {
+                    "+" => Opcode::Add,
+                    "-" => Opcode::Sub,
+                    "*" => Opcode::Mul,
+                    "/" => Opcode::Div,
+                    "==" => Opcode::Equal,
+                    "!=" => Opcode::NotEqual,
+                    ">" => Opcode::GreaterThan,
+                    "<" => Opcode::LessThan,
+                    op => return Err(unknown_operator(op, e.token.span)),
+                };
+                self.emit(op, &[]);
+            }
+            Expression::If(e) => self.compile_if_expression(e)?,
+            Expression::Function(e) => self.compile_function_literal(e)?,
+            Expression::Call(e) => {
+                self.compile_expression(&e.function)?;
+                for arg in e.arguments.iter() {
+                    self.compile_expression(arg)?;
+                }
+                let count = check_limit(e.arguments.len(), u8::MAX, "arguments", e.token.span)?;
+                self.emit(Opcode::Call, &[count]);
+            }
+            Expression::Array(e) => {
+                for element in e.elements.iter() {
+                    self.compile_expression(element)?;
+                }
+                let count = check_limit(e.elements.len(), u16::MAX, "elements", e.token.span)?;
+                self.emit(Opcode::Array, &[count]);
+            }
+            Expression::Index(e) => {
+                self.compile_expression(&e.left)?;
+                self.compile_expression(&e.index)?;
+                self.emit(Opcode::Index, &[]);
+            }
+            Expression::Hash(e) => self.compile_hash_literal(e)?,
+        }
+        Ok(())
+    }
+
+    fn compile_identifier(&mut self, ident: &Identifier) -> Result<(), Box<Diagnostic>> {
+        match self.local_slot(&ident.value) {
+            Some((0, slot)) => self.emit(Opcode::GetLocal, &[slot]),
+            Some((depth, slot)) => {
+                let depth = check_limit(depth, u8::MAX, "nested functions", ident.token.span)?;
+                self.emit(Opcode::GetOuter, &[depth, slot])
+            }
+            None => {
+                let slot = self.global_slot(ident)?;
+                self.emit(Opcode::GetGlobal, &[slot])
+            }
+        };
+        Ok(())
+    }
+
+    fn compile_if_expression(&mut self, ie: &IfExpression) -> Result<(), Box<Diagnostic>> {
+        self.compile_expression(&ie.condition)?;
+        // The jump targets are patched in once they are known.
+        let jump_not_truthy = self.emit(Opcode::JumpNotTruthy, &[0]);
+        self.compile_block(&ie.consequence)?;
+        let jump = self.emit(Opcode::Jump, &[0]);
+
+        let alternative = self.jump_target(ie.token.span)?;
+        self.change_operand(jump_not_truthy, alternative);
+        match &ie.alternative {
+            Some(block) => self.compile_block(block)?,
+            None => {
+                self.emit(Opcode::Null, &[]);
+            }
+        }
+
+        let end = self.jump_target(ie.token.span)?;
+        self.change_operand(jump, end);
+        Ok(())
+    }
+
+    fn compile_block(&mut self, block: &BlockStatement) -> Result<(), Box<Diagnostic>> {
+        self.compile_body(&block.statements)
+    }
+
+    fn compile_function_literal(&mut self, fl: &FunctionLiteral) -> Result<(), Box<Diagnostic>> {
+        let locals = hoist_locals(fl);
+        check_limit(locals.len(), u16::MAX as usize + 1, "locals", fl.token.span)?;
+        let line = self.scope().line;
+        self.scopes.push(CompilationScope {
+            locals,
+            line,
+            ..CompilationScope::default()
+        });
+        self.compile_block(&fl.body)?;
+        self.emit(Opcode::ReturnValue, &[]);
+        let scope = self.scopes.pop().unwrap();
+
+        let params: Vec<String> = fl.parameters.iter().map(|p| p.string()).collect();
+        let function = CompiledFunction {
+            instructions: scope.instructions,
+            num_parameters: fl.parameters.len(),
+            locals: scope.locals,
+            source: format!("fn({}) {}", params.join(", "), fl.body.string()),
+            lines: scope.lines,
+        };
+        let index =
+            self.add_constant(Object::CompiledFunction(Rc::new(function)), fl.token.span)?;
+        self.emit(Opcode::Closure, &[index]);
+        Ok(())
+    }
+
+    fn compile_hash_literal(&mut self, hl: &HashLiteral) -> Result<(), Box<Diagnostic>> {
+        for (key, value) in hl.pairs.iter() {
+            self.compile_expression(key)?;
+            // The evaluator rejects a key before evaluating its value, so
+            // keys that are not literals are checked straight away.
+            if !matches!(
+                key,
+                Expression::IntegerLiteral(_)
+                    | Expression::StringLiteral(_)
+                    | Expression::Boolean(_)
+            ) {
+                self.emit(Opcode::HashKey, &[]);
+            }
+            self.compile_expression(value)?;
+        }
+        let count = check_limit(hl.pairs.len(), u16::MAX, "pairs", hl.token.span)?;
+        self.emit(Opcode::Hash, &[count]);
+        Ok(())
+    }
+
+    /// Finds `name` among the locals of the functions being compiled,
+    /// returning how many functions out it is and its slot.
+    fn local_slot(&self, name: &str) -> Option<(usize, usize)> {
+        self.scopes[1..]
+            .iter()
+            .rev()
+            .enumerate()
+            .find_map(|(depth, scope)| {
+                let slot = scope.locals.iter().rposition(|local| local == name)?;
+                Some((depth, slot))
+            })
+    }
+
+    /// Returns the global slot for `ident`, allocating one the first time
+    /// the name is used.
+    fn global_slot(&mut self, ident: &Identifier) -> Result<usize, Box<Diagnostic>> {
+        if let Some(slot) = self
+            .globals
+            .iter()
+            .position(|global| *global == ident.value)
+        {
+            return Ok(slot);
+        }
+        check_limit(self.globals.len(), u16::MAX, "globals", ident.token.span)?;
+        self.globals.push(ident.value.clone());
+        Ok(self.globals.len() - 1)
+    }
+
+    fn add_constant(&mut self, obj: Object, span: Span) -> Result<usize, Box<Diagnostic>> {
+        check_limit(self.constants.len(), u16::MAX, "constants", span)?;
+        self.constants.push(obj);
+        Ok(self.constants.len() - 1)
+    }
+
+    fn scope(&mut self) -> &mut CompilationScope {
+        self.scopes.last_mut().unwrap()
+    }
+
+    fn instructions(&mut self) -> &mut Instructions {
+        &mut self.scope().instructions
+    }
+
+    /// Appends an instruction, returning its position.
+    fn emit(&mut self, op: Opcode, operands: &[usize]) -> usize {
+        let scope = self.scope();
+        let position = scope.instructions.len();
+        // Line 0 is the start of an empty program, which has no lines.
+        let new_line = scope
+            .lines
+            .last()
+            .is_none_or(|&(_, line)| line != scope.line);
+        if new_line && scope.line != 0 {
+            scope.lines.push((position, scope.line));
+        }
+        scope.instructions.extend(make(op, operands));
+        position
+    }
+
+    /// Returns the position of the next instruction, for a jump to it.
+    fn jump_target(&mut self, span: Span) -> Result<usize, Box<Diagnostic>> {
+        let position = self.instructions().len();
+        check_limit(
+            position,
+            u16::MAX as usize,
+            "bytes of code in a function",
+            span,
+        )
+    }
+
+    fn change_operand(&mut self, position: usize, operand: usize) {
+        let instructions = self.instructions();
+        let op = Opcode::from_byte(instructions[position]).unwrap();
+        let instruction = make(op, &[operand]);
+        instructions[position..position + instruction.len()].copy_from_slice(&instruction);
+    }
+}
+
+/// Collects the locals of a function: its parameters, then every name
+/// bound by `let` in its body, including inside `if` blocks but not inside
+/// nested functions.
+fn hoist_locals(fl: &FunctionLiteral) -> Vec<String> {
+    let mut locals: Vec<String> = fl.parameters.iter().map(|p| p.value.clone()).collect();
+    collect_lets(&fl.body.statements, &mut locals);
+    locals
+}
+
+fn collect_lets(statements: &[Statement], locals: &mut Vec<String>) {
+    for stmt in statements.iter() {
+        match stmt {
+            Statement::Let(s) => {
+                collect_lets_in(&s.value, locals);
+                if !locals.contains(&s.name.value) {
+                    locals.push(s.name.value.clone());
+                }
+            }
+            Statement::Return(s) => collect_lets_in(&s.return_value, locals),
+            Statement::Expression(s) => collect_lets_in(&s.expression, locals),
+            Statement::Error(_) => {}
+        }
+    }
+}
+
+fn collect_lets_in(exp: &Expression, locals: &mut Vec<String>) {
+    match exp {
+        Expression::Prefix(e) => collect_lets_in(&e.right, locals),
+        Expression::Infix(e) => {
+            collect_lets_in(&e.left, locals);
+            collect_lets_in(&e.right, locals);
+        }
+        Expression::If(e) => {
+            collect_lets_in(&e.condition, locals);
+            collect_lets(&e.consequence.statements, locals);
+            if let Some(alt) = &e.alternative {
+                collect_lets(&alt.statements, locals);
+            }
+        }
+        Expression::Call(e) => {
+            collect_lets_in(&e.function, locals);
+            for arg in e.arguments.iter() {
+                collect_lets_in(arg, locals);
+            }
+        }
+        Expression::Array(e) => {
+            for element in e.elements.iter() {
+                collect_lets_in(element, locals);
+            }
+        }
+        Expression::Index(e) => {
+            collect_lets_in(&e.left, locals);
+            collect_lets_in(&e.index, locals);
+        }
+        Expression::Hash(e) => {
+            for (key, value) in e.pairs.iter() {
+                collect_lets_in(key, locals);
+                collect_lets_in(value, locals);
+            }
+        }
+        // A nested function binds its lets in its own locals.
+        Expression::Function(_)
+        | Expression::Identifier(_)
+        | Expression::IntegerLiteral(_)
+        | Expression::StringLiteral(_)
+        | Expression::Boolean(_) => {}
+    }
+}
+
+fn check_limit(
+    value: usize,
+    max: impl Into<usize>,
+    what: &str,
+    span: Span,
+) -> Result<usize, Box<Diagnostic>> {
+    let max = max.into();
+    if value > max {
+        return Err(Box::new(Diagnostic::error(
+            "E0006",
+            format!("too many {} to compile: the limit is {}", what, max),
+            span,
+        )));
+    }
+    Ok(value)
+}
+
+fn unknown_operator(operator: &str, span: Span) -> Box<Diagnostic> {
+    Box::new(Diagnostic::error(
+        "E0006",
+        format!("unknown operator: {}", operator),
+        span,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::code::{make, Instructions, Opcode};
+    use crate::compiler::{Bytecode, Compiler};
+    use crate::lexer::Lexer;
+    use crate::object::Object;
+    use crate::parser::Parser;
+
+    fn compile(input: &str) -> Bytecode {
+        let mut p = Parser::new(Lexer::new(input.to_string()));
+        let program = p.parse_program();
+        assert!(p.errors.is_empty(), "parser errors: {:?}", p.errors);
+        Compiler::new().compile(&program).unwrap()
+    }
+
+    fn concat(instructions: Vec<Vec<u8>>) -> Instructions {
+        Instructions(instructions.concat())
+    }
+
+    fn function(constant: &Object) -> (&Instructions, &[String]) {
+        match constant {
+            Object::CompiledFunction(f) => (&f.instructions, &f.locals),
+            obj => panic!("expected compiled function, got {:?}", obj),
+        }
+    }
+
+    #[test]
+    fn test_expressions() {
+        let tests = vec![
+            (
+                "1 + 2",
+                vec![
+                    make(Opcode::Constant, &[0]),
+                    make(Opcode::Constant, &[1]),
+                    make(Opcode::Add, &[]),
+                    make(Opcode::ReturnValue, &[]),
+                ],
+            ),
+            (
+                "1; 2 < 3",
+                vec![
+                    make(Opcode::Constant, &[0]),
+                    make(Opcode::Pop, &[]),
+                    make(Opcode::Constant, &[1]),
+                    make(Opcode::Constant, &[2]),
+                    make(Opcode::LessThan, &[]),
+                    make(Opcode::ReturnValue, &[]),
+                ],
+            ),
+            (
+                "!true == -1",
+                vec![
+                    make(Opcode::True, &[]),
+                    make(Opcode::Bang, &[]),
+                    make(Opcode::Constant, &[0]),
+                    make(Opcode::Minus, &[]),
+                    make(Opcode::Equal, &[]),
+                    make(Opcode::ReturnValue, &[]),
+                ],
+            ),
+            (
+                "[1, \"a\"][0]",
+                vec![
+                    make(Opcode::Constant, &[0]),
+                    make(Opcode::Constant, &[1]),
+                    make(Opcode::Array, &[2]),
+                    make(Opcode::Constant, &[2]),
+                    make(Opcode::Index, &[]),
+                    make(Opcode::ReturnValue, &[]),
+                ],
+            ),
+            (
+                "{1: 2, x: 3}",
+                vec![
+                    make(Opcode::Constant, &[0]),
+                    make(Opcode::Constant, &[1]),
+                    make(Opcode::GetGlobal, &[0]),
+                    make(Opcode::HashKey, &[]),
+                    make(Opcode::Constant, &[2]),
+                    make(Opcode::Hash, &[2]),
+                    make(Opcode::ReturnValue, &[]),
+                ],
+            ),
+            (
+                "",
+                vec![make(Opcode::Null, &[]), make(Opcode::ReturnValue, &[])],
+            ),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(
+                compile(input).main.instructions,
+                concat(expected),
+                "{}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn test_conditionals() {
+        let bytecode = compile("if (true) { 10 }; 3333;");
+        let expected = concat(vec![
+            // 0000
+            make(Opcode::True, &[]),
+            // 0001
+            make(Opcode::JumpNotTruthy, &[10]),
+            // 0004
+            make(Opcode::Constant, &[0]),
+            // 0007
+            make(Opcode::Jump, &[11]),
+            // 0010
+            make(Opcode::Null, &[]),
+            // 0011
+            make(Opcode::Pop, &[]),
+            make(Opcode::Constant, &[1]),
+            make(Opcode::ReturnValue, &[]),
+        ]);
+        assert_eq!(bytecode.main.instructions, expected);
+
+        let bytecode = compile("if (x) { let y = 1; } else { 2 }");
+        let expected = concat(vec![
+            make(Opcode::GetGlobal, &[0]),
+            make(Opcode::JumpNotTruthy, &[16]),
+            make(Opcode::Constant, &[0]),
+            make(Opcode::SetGlobal, &[1]),
+            make(Opcode::Null, &[]),
+            make(Opcode::Jump, &[19]),
+            make(Opcode::Constant, &[1]),
+            make(Opcode::ReturnValue, &[]),
+        ]);
+        assert_eq!(bytecode.main.instructions, expected);
+        assert_eq!(bytecode.globals, vec!["x", "y"]);
+    }
+
+    #[test]
+    fn test_global_let_statements() {
+        let bytecode = compile("let one = 1; let two = one; two; len");
+        let expected = concat(vec![
+            make(Opcode::Constant, &[0]),
+            make(Opcode::SetGlobal, &[0]),
+            make(Opcode::GetGlobal, &[0]),
+            make(Opcode::SetGlobal, &[1]),
+            make(Opcode::GetGlobal, &[1]),
+            make(Opcode::Pop, &[]),
+            make(Opcode::GetGlobal, &[2]),
+            make(Opcode::ReturnValue, &[]),
+        ]);
+        assert_eq!(bytecode.main.instructions, expected);
+        assert_eq!(bytecode.globals, vec!["one", "two", "len"]);
+    }
+
+    #[test]
+    fn test_functions() {
+        let bytecode = compile("fn(a) { if (a) { let b = a; }; return b; }(1)");
+        assert_eq!(
+            bytecode.main.instructions,
+            concat(vec![
+                make(Opcode::Closure, &[0]),
+                make(Opcode::Constant, &[1]),
+                make(Opcode::Call, &[1]),
+                make(Opcode::ReturnValue, &[]),
+            ])
+        );
+
+        let (instructions, locals) = function(&bytecode.constants[0]);
+        assert_eq!(locals, ["a", "b"]);
+        assert_eq!(
+            *instructions,
+            concat(vec![
+                make(Opcode::GetLocal, &[0]),
+                make(Opcode::JumpNotTruthy, &[16]),
+                make(Opcode::GetLocal, &[0]),
+                make(Opcode::SetLocal, &[1]),
+                make(Opcode::Null, &[]),
+                make(Opcode::Jump, &[17]),
+                make(Opcode::Null, &[]),
+                make(Opcode::Pop, &[]),
+                make(Opcode::GetLocal, &[1]),
+                make(Opcode::ReturnValue, &[]),
+                make(Opcode::ReturnValue, &[]),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_closures() {
+        let bytecode = compile("fn(a) { fn(b) { fn(c) { a + b + c + d } } }");
+
+        let (instructions, locals) = function(&bytecode.constants[0]);
+        assert_eq!(locals, ["c"]);
+        assert_eq!(
+            *instructions,
+            concat(vec![
+                make(Opcode::GetOuter, &[2, 0]),
+                make(Opcode::GetOuter, &[1, 0]),
+                make(Opcode::Add, &[]),
+                make(Opcode::GetLocal, &[0]),
+                make(Opcode::Add, &[]),
+                make(Opcode::GetGlobal, &[0]),
+                make(Opcode::Add, &[]),
+                make(Opcode::ReturnValue, &[]),
+            ])
+        );
+
+        let (instructions, _) = function(&bytecode.constants[1]);
+        assert_eq!(
+            *instructions,
+            concat(vec![
+                make(Opcode::Closure, &[0]),
+                make(Opcode::ReturnValue, &[]),
+            ])
+        );
+        assert_eq!(bytecode.globals, vec!["d"]);
+    }
+
+    #[test]
+    fn test_state_carries_over() {
+        let mut compiler = Compiler::new();
+        for (input, globals) in [("let a = 1;", 1), ("let b = a;", 2), ("a + b", 2)] {
+            let mut p = Parser::new(Lexer::new(input.to_string()));
+            let bytecode = compiler.compile(&p.parse_program()).unwrap();
+            assert_eq!(bytecode.globals.len(), globals, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_syntax_errors_are_not_compiled() {
+        let mut p = Parser::new(Lexer::new(String::from("let = 1;")));
+        let program = p.parse_program();
+        let errors = Compiler::new().compile(&program).unwrap_err();
+        assert_eq!(errors[0].code, "E0006");
+    }
+
+    #[test]
+    fn test_lines() {
+        let bytecode = compile("let x = 1;\nlet f = fn() {\n  x\n};\nf()");
+        assert_eq!(bytecode.main.lines, [(0, 1), (6, 2), (12, 5)]);
+        let tests = vec![(0, Some(1)), (3, Some(1)), (6, Some(2)), (15, Some(5))];
+        for (offset, line) in tests {
+            assert_eq!(bytecode.main.line(offset), line, "offset {}", offset);
+        }
+
+        // The function's closing return belongs to the statement it is in.
+        match &bytecode.constants[1] {
+            Object::CompiledFunction(f) => assert_eq!(f.lines, [(0, 3), (3, 2)]),
+            obj => panic!("expected compiled function, got {:?}", obj),
+        }
+    }
+}
@@ -0,0 +1,555 @@
+//! A lossless concrete syntax tree.
+//!
+//! Where the [`ast`](crate::ast) keeps only what evaluation needs, the tree
+//! built here keeps every token of the source together with the whitespace
+//! and comments around it, so printing it gives back the original text byte
+//! for byte. It is meant for tools that rewrite source, such as a formatter.
+//!
+//! The tree is built even for malformed input: tokens that fit nowhere are
+//! wrapped in [`SyntaxKind::Error`] nodes. Use [`Parser`](crate::parser::Parser)
+//! to find out what is wrong with such input.
+
+use std::fmt;
+
+use crate::{
+    lexer::Lexer,
+    parser::{precedence_of, Precedence},
+    token::{Span, Token, TokenKind},
+};
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum SyntaxKind {
+    Program,
+    LetStatement,
+    ReturnStatement,
+    ExpressionStatement,
+    BlockStatement,
+    Identifier,
+    Literal, // integers, strings and booleans
+    PrefixExpression,
+    InfixExpression,
+    GroupedExpression,
+    IfExpression,
+    FunctionLiteral,
+    ParameterList,
+    CallExpression,
+    ArgumentList,
+    ArrayLiteral,
+    IndexExpression,
+    HashLiteral,
+    HashPair,
+    Error,
+}
+
+/// A token as it was written. `leading` holds the trivia between the
+/// previous token's trailing trivia and this token; `trailing` holds the
+/// trivia after it up to the end of its line.
+#[derive(Debug, PartialEq, Clone)]
+pub struct SyntaxToken {
+    pub token: Token,
+    pub text: String,
+    pub leading: Vec<Token>,
+    pub trailing: Vec<Token>,
+}
+
+impl SyntaxToken {
+    pub fn kind(&self) -> &TokenKind {
+        &self.token.token_kind
+    }
+
+    pub fn span(&self) -> Span {
+        self.token.span
+    }
+}
+
+impl fmt::Display for SyntaxToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for trivia in self.leading.iter() {
+            f.write_str(&trivia.literal)?;
+        }
+        f.write_str(&self.text)?;
+        for trivia in self.trailing.iter() {
+            f.write_str(&trivia.literal)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum SyntaxElement {
+    Node(SyntaxNode),
+    Token(SyntaxToken),
+}
+
+impl fmt::Display for SyntaxElement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SyntaxElement::Node(node) => node.fmt(f),
+            SyntaxElement::Token(token) => token.fmt(f),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct SyntaxNode {
+    pub kind: SyntaxKind,
+    pub children: Vec<SyntaxElement>,
+}
+
+impl SyntaxNode {
+    pub fn child_nodes(&self) -> impl Iterator<Item = &SyntaxNode> {
+        self.children.iter().filter_map(|child| match child {
+            SyntaxElement::Node(node) => Some(node),
+            SyntaxElement::Token(_) => None,
+        })
+    }
+
+    pub fn child_tokens(&self) -> impl Iterator<Item = &SyntaxToken> {
+        self.children.iter().filter_map(|child| match child {
+            SyntaxElement::Token(token) => Some(token),
+            SyntaxElement::Node(_) => None,
+        })
+    }
+}
+
+// Prints the exact source the node was parsed from.
+impl fmt::Display for SyntaxNode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for child in self.children.iter() {
+            child.fmt(f)?;
+        }
+        Ok(())
+    }
+}
+
+/// Parses `input` into a [`SyntaxKind::Program`] node. The last child of
+/// the program is always the `Eof` token, which holds any trailing trivia.
+pub fn parse(input: &str) -> SyntaxNode {
+    let mut p = CstParser::new(input);
+    while !p.at(TokenKind::Eof) {
+        p.statement();
+    }
+    p.bump();
+
+    let (kind, children) = p.stack.pop().unwrap();
+    SyntaxNode { kind, children }
+}
+
+struct CstParser {
+    tokens: Vec<SyntaxToken>,
+    position: usize,
+    // Open nodes, innermost last; the program is at the bottom.
+    stack: Vec<(SyntaxKind, Vec<SyntaxElement>)>,
+}
+
+impl CstParser {
+    fn new(input: &str) -> CstParser {
+        CstParser {
+            tokens: attach_trivia(input),
+            position: 0,
+            stack: vec![(SyntaxKind::Program, vec![])],
+        }
+    }
+
+    fn current(&self) -> &TokenKind {
+        self.tokens[self.position].kind()
+    }
+
+    fn at(&self, kind: TokenKind) -> bool {
+        *self.current() == kind
+    }
+
+    fn bump(&mut self) {
+        let token = self.tokens[self.position].clone();
+        if self.position + 1 < self.tokens.len() {
+            self.position += 1;
+        }
+        self.push(SyntaxElement::Token(token));
+    }
+
+    fn expect(&mut self, kind: TokenKind) {
+        if self.at(kind) {
+            self.bump();
+        }
+    }
+
+    fn push(&mut self, element: SyntaxElement) {
+        self.stack.last_mut().unwrap().1.push(element);
+    }
+
+    fn start_node(&mut self, kind: SyntaxKind) {
+        self.stack.push((kind, vec![]));
+    }
+
+    /// Returns a marker that `start_node_at` can later wrap from, for nodes
+    /// such as infix expressions whose kind is only known after their first
+    /// child has been parsed.
+    fn checkpoint(&self) -> usize {
+        self.stack.last().unwrap().1.len()
+    }
+
+    fn start_node_at(&mut self, checkpoint: usize, kind: SyntaxKind) {
+        let children = self.stack.last_mut().unwrap().1.split_off(checkpoint);
+        self.stack.push((kind, children));
+    }
+
+    fn finish_node(&mut self) {
+        let (kind, children) = self.stack.pop().unwrap();
+        self.push(SyntaxElement::Node(SyntaxNode { kind, children }));
+    }
+
+    fn bump_error(&mut self) {
+        self.start_node(SyntaxKind::Error);
+        self.bump();
+        self.finish_node();
+    }
+
+    fn statement(&mut self) {
+        let position = self.position;
+
+        match self.current() {
+            TokenKind::Let => {
+                self.start_node(SyntaxKind::LetStatement);
+                self.bump();
+                if self.at(TokenKind::Ident) {
+                    self.start_node(SyntaxKind::Identifier);
+                    self.bump();
+                    self.finish_node();
+                }
+                self.expect(TokenKind::Assign);
+                self.expression(Precedence::Lowest);
+            }
+            TokenKind::Return => {
+                self.start_node(SyntaxKind::ReturnStatement);
+                self.bump();
+                self.expression(Precedence::Lowest);
+            }
+            _ => {
+                self.start_node(SyntaxKind::ExpressionStatement);
+                self.expression(Precedence::Lowest);
+            }
+        }
+        self.expect(TokenKind::Semicolon);
+        self.finish_node();
+
+        // No statement starts here: drop the empty node, keep the token and
+        // move on.
+        if self.position == position {
+            self.stack.last_mut().unwrap().1.pop();
+            self.bump_error();
+        }
+    }
+
+    fn block(&mut self) {
+        self.start_node(SyntaxKind::BlockStatement);
+        self.bump();
+        while !self.at(TokenKind::RBrace) && !self.at(TokenKind::Eof) {
+            self.statement();
+        }
+        self.expect(TokenKind::RBrace);
+        self.finish_node();
+    }
+
+    fn expression(&mut self, precedence: Precedence) {
+        let checkpoint = self.checkpoint();
+
+        match self.current() {
+            TokenKind::Ident => {
+                self.start_node(SyntaxKind::Identifier);
+                self.bump();
+                self.finish_node();
+            }
+            TokenKind::Int | TokenKind::String | TokenKind::True | TokenKind::False => {
+                self.start_node(SyntaxKind::Literal);
+                self.bump();
+                self.finish_node();
+            }
+            TokenKind::Bang | TokenKind::Minus => {
+                self.start_node(SyntaxKind::PrefixExpression);
+                self.bump();
+                self.expression(Precedence::Prefix);
+                self.finish_node();
+            }
+            TokenKind::LParen => {
+                self.start_node(SyntaxKind::GroupedExpression);
+                self.bump();
+                self.expression(Precedence::Lowest);
+                self.expect(TokenKind::RParen);
+                self.finish_node();
+            }
+            TokenKind::If => self.if_expression(),
+            TokenKind::Function => {
+                self.start_node(SyntaxKind::FunctionLiteral);
+                self.bump();
+                if self.at(TokenKind::LParen) {
+                    self.list(SyntaxKind::ParameterList, TokenKind::RParen, |p| {
+                        if p.at(TokenKind::Ident) {
+                            p.start_node(SyntaxKind::Identifier);
+                            p.bump();
+                            p.finish_node();
+                        }
+                    });
+                }
+                if self.at(TokenKind::LBrace) {
+                    self.block();
+                }
+                self.finish_node();
+            }
+            TokenKind::LBracket => {
+                self.list(SyntaxKind::ArrayLiteral, TokenKind::RBracket, |p| {
+                    p.expression(Precedence::Lowest)
+                });
+            }
+            TokenKind::LBrace => {
+                self.list(SyntaxKind::HashLiteral, TokenKind::RBrace, |p| {
+                    p.start_node(SyntaxKind::HashPair);
+                    p.expression(Precedence::Lowest);
+                    p.expect(TokenKind::Colon);
+                    p.expression(Precedence::Lowest);
+                    p.finish_node();
+                });
+            }
+            // Closing tokens end whatever encloses the missing expression.
+            TokenKind::RParen
+            | TokenKind::RBracket
+            | TokenKind::RBrace
+            | TokenKind::Comma
+            | TokenKind::Semicolon
+            | TokenKind::Eof => return,
+            _ => {
+                self.bump_error();
+                return;
+            }
+        }
+
+        while precedence < precedence_of(self.current()) {
+            match self.current() {
+                TokenKind::LParen => {
+                    self.start_node_at(checkpoint, SyntaxKind::CallExpression);
+                    self.list(SyntaxKind::ArgumentList, TokenKind::RParen, |p| {
+                        p.expression(Precedence::Lowest)
+                    });
+                }
+                TokenKind::LBracket => {
+                    self.start_node_at(checkpoint, SyntaxKind::IndexExpression);
+                    self.bump();
+                    self.expression(Precedence::Lowest);
+                    self.expect(TokenKind::RBracket);
+                }
+                kind => {
+                    let precedence = precedence_of(kind);
+                    self.start_node_at(checkpoint, SyntaxKind::InfixExpression);
+                    self.bump();
+                    self.expression(precedence);
+                }
+            }
+            self.finish_node();
+        }
+    }
+
+    fn if_expression(&mut self) {
+        self.start_node(SyntaxKind::IfExpression);
+        self.bump();
+        self.expect(TokenKind::LParen);
+        self.expression(Precedence::Lowest);
+        self.expect(TokenKind::RParen);
+        if self.at(TokenKind::LBrace) {
+            self.block();
+        }
+        if self.at(TokenKind::Else) {
+            self.bump();
+            if self.at(TokenKind::LBrace) {
+                self.block();
+            }
+        }
+        self.finish_node();
+    }
+
+    // Parses an opening token, comma separated items and the closing token.
+    fn list(&mut self, kind: SyntaxKind, close: TokenKind, item: fn(&mut CstParser)) {
+        self.start_node(kind);
+        self.bump();
+        while !self.at(close.clone()) && !self.at(TokenKind::Eof) {
+            item(self);
+            if !self.at(TokenKind::Comma) {
+                break;
+            }
+            self.bump();
+        }
+        self.expect(close);
+        self.finish_node();
+    }
+}
+
+// Lexes `input` keeping trivia and hands it out to the tokens around it.
+// Trivia up to the first line break after a token trails that token; the
+// rest leads the next one.
+fn attach_trivia(input: &str) -> Vec<SyntaxToken> {
+    let mut lexer = Lexer::new(input.to_string())
+        .with_comments(true)
+        .with_whitespace(true);
+    let mut tokens: Vec<SyntaxToken> = vec![];
+    let mut leading = vec![];
+    let mut trailing = true;
+
+    loop {
+        let token = lexer.next_token();
+        match token.token_kind {
+            TokenKind::Whitespace | TokenKind::Comment => {
+                if token.literal.contains('\n') {
+                    trailing = false;
+                }
+                match tokens.last_mut() {
+                    Some(last) if trailing => last.trailing.push(token),
+                    _ => leading.push(token),
+                }
+            }
+            _ => {
+                let eof = token.token_kind == TokenKind::Eof;
+                tokens.push(SyntaxToken {
+                    text: input[token.span.start..token.span.end].to_string(),
+                    token,
+                    leading: std::mem::take(&mut leading),
+                    trailing: vec![],
+                });
+                trailing = true;
+                if eof {
+                    return tokens;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cst::{parse, SyntaxElement, SyntaxKind, SyntaxNode};
+    use crate::token::TokenKind;
+
+    // Renders the tree as nested kinds with the tokens' text, e.g.
+    // `(LetStatement let (Identifier x) = (Literal 1) ;)`.
+    fn sexpr(node: &SyntaxNode) -> String {
+        let mut parts = vec![format!("{:?}", node.kind)];
+        for child in node.children.iter() {
+            match child {
+                SyntaxElement::Node(node) => parts.push(sexpr(node)),
+                SyntaxElement::Token(token) if !token.text.is_empty() => {
+                    parts.push(token.text.clone())
+                }
+                SyntaxElement::Token(_) => {}
+            }
+        }
+        format!("({})", parts.join(" "))
+    }
+
+    #[test]
+    fn test_lossless_round_trip() {
+        let tests = vec![
+            "",
+            "   \n\n",
+            "let x = 5;",
+            "let   add = fn(a,b){\n\treturn a+b; // sum\n};\r\nadd(1,\n  2)\n",
+            "/* header /* nested */ */\nlet h = {\"k\": [1, 2][0], true: !x};\n// tail",
+            "let größe = \"héllo 日本\"; größe",
+            "if (x < y) { x } else { y }",
+            // Broken input must survive just as well.
+            "let = ; }}} let x = (1 2",
+            "fn(x { let = x; } ] , \"unterminated",
+            "let a = 1 /* never closed",
+            "@ € let b = [1; 2];",
+        ];
+
+        for input in tests {
+            assert_eq!(parse(input).to_string(), input);
+        }
+    }
+
+    #[test]
+    fn test_tree_structure() {
+        let tests = vec![
+            (
+                "let x = 1 + 2 * 3;",
+                "(Program (LetStatement let (Identifier x) = (InfixExpression (Literal 1) + \
+                 (InfixExpression (Literal 2) * (Literal 3))) ;))",
+            ),
+            (
+                "add(a, b[1])",
+                "(Program (ExpressionStatement (CallExpression (Identifier add) \
+                 (ArgumentList ( (Identifier a) , (IndexExpression (Identifier b) [ (Literal 1) ]) )))))",
+            ),
+            (
+                "if (!x) { return 1; } else { 2 }",
+                "(Program (ExpressionStatement (IfExpression if ( (PrefixExpression ! (Identifier x)) ) \
+                 (BlockStatement { (ReturnStatement return (Literal 1) ;) }) else \
+                 (BlockStatement { (ExpressionStatement (Literal 2)) }))))",
+            ),
+            (
+                "fn(a, b) { {a: b} }",
+                "(Program (ExpressionStatement (FunctionLiteral fn (ParameterList ( (Identifier a) , \
+                 (Identifier b) )) (BlockStatement { (ExpressionStatement (HashLiteral { \
+                 (HashPair (Identifier a) : (Identifier b)) })) }))))",
+            ),
+            (
+                "(1 + 2) * 3",
+                "(Program (ExpressionStatement (InfixExpression (GroupedExpression ( \
+                 (InfixExpression (Literal 1) + (Literal 2)) )) * (Literal 3))))",
+            ),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(sexpr(&parse(input)), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_error_nodes() {
+        assert_eq!(
+            sexpr(&parse("let = @; }")),
+            "(Program (LetStatement let = (Error @) ;) (Error }))"
+        );
+        assert_eq!(
+            sexpr(&parse("if (x) { ] }")),
+            "(Program (ExpressionStatement (IfExpression if ( (Identifier x) ) \
+             (BlockStatement { (Error ]) }))))"
+        );
+    }
+
+    #[test]
+    fn test_trivia_attachment() {
+        let program = parse("// greeting\nlet x = 1; // one\n\n  x /* two */\n");
+        let tokens: Vec<_> = program
+            .child_nodes()
+            .flat_map(|node| node.children.iter())
+            .filter_map(|child| match child {
+                SyntaxElement::Token(token) => Some(token),
+                SyntaxElement::Node(_) => None,
+            })
+            .collect();
+
+        let let_token = tokens[0];
+        assert_eq!(let_token.text, "let");
+        let leading: Vec<&str> = let_token
+            .leading
+            .iter()
+            .map(|t| t.literal.as_str())
+            .collect();
+        assert_eq!(leading, vec!["// greeting", "\n"]);
+
+        let semicolon = tokens[2];
+        assert_eq!(semicolon.text, ";");
+        let trailing: Vec<&str> = semicolon
+            .trailing
+            .iter()
+            .map(|t| t.literal.as_str())
+            .collect();
+        assert_eq!(trailing, vec![" ", "// one"]);
+
+        let eof = program.child_tokens().last().unwrap();
+        assert_eq!(eof.kind(), &TokenKind::Eof);
+        assert_eq!(eof.to_string(), "\n");
+
+        let x = program.child_nodes().nth(1).unwrap();
+        assert_eq!(x.kind, SyntaxKind::ExpressionStatement);
+        assert_eq!(x.to_string(), "\n\n  x /* two */");
+    }
+}
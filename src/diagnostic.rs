@@ -0,0 +1,331 @@
+use std::fmt;
+
+use crate::token::Span;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl Severity {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        }
+    }
+
+    fn color(&self) -> &'static str {
+        match self {
+            Severity::Error => RED,
+            Severity::Warning => YELLOW,
+        }
+    }
+}
+
+/// A span of source with a short explanation attached.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Label {
+    pub span: Span,
+    pub message: String,
+}
+
+/// A problem found in a Monkey program, with enough detail to point at the
+/// offending source.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub code: &'static str,
+    pub message: String,
+    pub primary: Label,
+    pub secondary: Vec<Label>,
+    pub notes: Vec<String>,
+}
+
+impl Diagnostic {
+    pub fn error(code: &'static str, message: String, span: Span) -> Diagnostic {
+        Diagnostic {
+            severity: Severity::Error,
+            code,
+            message,
+            primary: Label {
+                span,
+                message: String::new(),
+            },
+            secondary: vec![],
+            notes: vec![],
+        }
+    }
+
+    /// Sets the text printed under the primary span.
+    pub fn with_primary_label(mut self, message: String) -> Diagnostic {
+        self.primary.message = message;
+        self
+    }
+
+    pub fn with_label(mut self, span: Span, message: String) -> Diagnostic {
+        self.secondary.push(Label { span, message });
+        self
+    }
+
+    pub fn with_note(mut self, note: String) -> Diagnostic {
+        self.notes.push(note);
+        self
+    }
+}
+
+// The one-line form, `3:14: expected Ident, got Int`.
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.primary.span, self.message)
+    }
+}
+
+const RED: &str = "\x1b[31m";
+const YELLOW: &str = "\x1b[33m";
+const BLUE: &str = "\x1b[34m";
+const BOLD: &str = "\x1b[1m";
+const RESET: &str = "\x1b[0m";
+
+/// Renders diagnostics against the source they were reported for:
+///
+/// ```text
+/// error[E0001]: expected Ident, got Int
+///  --> script.mk:4:5
+///   |
+/// 4 | let 5 = z;
+///   |     ^ expected Ident
+/// ```
+pub struct Renderer<'a> {
+    name: &'a str,
+    source: &'a str,
+    color: bool,
+}
+
+impl<'a> Renderer<'a> {
+    pub fn new(name: &'a str, source: &'a str) -> Renderer<'a> {
+        Renderer {
+            name,
+            source,
+            color: false,
+        }
+    }
+
+    /// Turns ANSI colors on or off; they are off by default.
+    pub fn with_color(mut self, color: bool) -> Renderer<'a> {
+        self.color = color;
+        self
+    }
+
+    pub fn render(&self, diagnostic: &Diagnostic) -> String {
+        let mut labels: Vec<(&Label, bool)> = vec![(&diagnostic.primary, true)];
+        labels.extend(diagnostic.secondary.iter().map(|l| (l, false)));
+        labels.sort_by_key(|(l, _)| (l.span.line, l.span.column));
+
+        let last_line = labels.iter().map(|(l, _)| l.span.line).max().unwrap_or(1);
+        let gutter = " ".repeat(last_line.to_string().len());
+
+        let mut out = String::new();
+        out.push_str(&self.paint(
+            &format!("{}[{}]", diagnostic.severity.as_str(), diagnostic.code),
+            &format!("{}{}", BOLD, diagnostic.severity.color()),
+        ));
+        out.push_str(&self.paint(&format!(": {}", diagnostic.message), BOLD));
+        out.push('\n');
+
+        out.push_str(&format!(
+            "{}{} {}:{}\n",
+            gutter,
+            self.paint("-->", BLUE),
+            self.name,
+            diagnostic.primary.span
+        ));
+        out.push_str(&format!("{} {}\n", gutter, self.paint("|", BLUE)));
+
+        let mut previous_line = None;
+        for (label, is_primary) in labels {
+            let line = label.span.line;
+            let text = self.line_text(line);
+
+            if previous_line != Some(line) {
+                if previous_line.is_some_and(|p| line > p + 1) {
+                    out.push_str(&format!("{}\n", self.paint("...", BLUE)));
+                }
+                let source_line = format!(
+                    "{} {} {}",
+                    self.paint(&format!("{:>width$}", line, width = gutter.len()), BLUE),
+                    self.paint("|", BLUE),
+                    text
+                );
+                out.push_str(source_line.trim_end());
+                out.push('\n');
+            }
+            previous_line = Some(line);
+
+            // Underline up to the end of the label's first line.
+            let line_start = self.line_start(line);
+            let line_end = line_start + text.len();
+            let start = label.span.start.clamp(line_start, line_end);
+            let end = label.span.end.clamp(start, line_end);
+            let offset = self.source[line_start..start].chars().count();
+            let width = self.source[start..end].chars().count().max(1);
+
+            let (marker, color) = if is_primary {
+                ("^", diagnostic.severity.color())
+            } else {
+                ("-", BLUE)
+            };
+            let mut underline = marker.repeat(width);
+            if !label.message.is_empty() {
+                underline.push(' ');
+                underline.push_str(&label.message);
+            }
+            out.push_str(&format!(
+                "{} {} {}{}\n",
+                gutter,
+                self.paint("|", BLUE),
+                " ".repeat(offset),
+                self.paint(&underline, color)
+            ));
+        }
+
+        for note in diagnostic.notes.iter() {
+            out.push_str(&format!(
+                "{} {} note: {}\n",
+                gutter,
+                self.paint("=", BLUE),
+                note
+            ));
+        }
+
+        out
+    }
+
+    fn paint(&self, text: &str, color: &str) -> String {
+        if self.color {
+            format!("{}{}{}", color, text, RESET)
+        } else {
+            text.to_string()
+        }
+    }
+
+    fn line_start(&self, line: usize) -> usize {
+        if line <= 1 {
+            return 0;
+        }
+        self.source
+            .match_indices('\n')
+            .nth(line - 2)
+            .map_or(self.source.len(), |(i, _)| i + 1)
+    }
+
+    fn line_text(&self, line: usize) -> &str {
+        let start = self.line_start(line);
+        let rest = &self.source[start..];
+        let text = rest.split('\n').next().unwrap_or("");
+        text.strip_suffix('\r').unwrap_or(text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::diagnostic::{Diagnostic, Renderer};
+    use crate::token::Span;
+
+    fn span(start: usize, end: usize, line: usize, column: usize) -> Span {
+        Span {
+            start,
+            end,
+            line,
+            column,
+        }
+    }
+
+    #[test]
+    fn test_display() {
+        let d = Diagnostic::error(
+            "E0001",
+            String::from("expected Ident, got Int"),
+            span(4, 5, 1, 5),
+        );
+
+        assert_eq!(d.to_string(), "1:5: expected Ident, got Int");
+    }
+
+    #[test]
+    fn test_render_primary_label() {
+        let source = "let x = 1;\nlet 55 = z;\n";
+        let d = Diagnostic::error(
+            "E0001",
+            String::from("expected Ident, got Int"),
+            span(15, 17, 2, 5),
+        )
+        .with_primary_label(String::from("expected Ident"));
+
+        assert_eq!(
+            Renderer::new("script.mk", source).render(&d),
+            "error[E0001]: expected Ident, got Int
+ --> script.mk:2:5
+  |
+2 | let 55 = z;
+  |     ^^ expected Ident
+"
+        );
+    }
+
+    #[test]
+    fn test_render_secondary_labels_and_notes() {
+        let source = "if (x) {\n  1\n\n\n";
+        let d = Diagnostic::error(
+            "E0005",
+            String::from("unclosed delimiter"),
+            span(16, 16, 5, 1),
+        )
+        .with_label(
+            span(7, 8, 1, 8),
+            String::from("unclosed delimiter opened here"),
+        )
+        .with_note(String::from("blocks must end with `}`"));
+
+        assert_eq!(
+            Renderer::new("t.mk", source).render(&d),
+            "error[E0005]: unclosed delimiter
+ --> t.mk:5:1
+  |
+1 | if (x) {
+  |        - unclosed delimiter opened here
+...
+5 |
+  | ^
+  = note: blocks must end with `}`
+"
+        );
+    }
+
+    #[test]
+    fn test_render_multibyte_line() {
+        let source = "let s = \"héllo\" + ;";
+        let d = Diagnostic::error(
+            "E0002",
+            String::from("expected an expression, found Semicolon"),
+            span(19, 20, 1, 19),
+        );
+
+        let rendered = Renderer::new("t.mk", source).render(&d);
+        assert!(
+            rendered.ends_with("1 | let s = \"héllo\" + ;\n  |                   ^\n"),
+            "{}",
+            rendered
+        );
+    }
+
+    #[test]
+    fn test_render_color() {
+        let d = Diagnostic::error("E0001", String::from("oops"), span(0, 1, 1, 1));
+        let rendered = Renderer::new("t.mk", "x").with_color(true).render(&d);
+
+        assert!(rendered.starts_with("\x1b[1m\x1b[31merror[E0001]\x1b[0m"));
+        assert!(rendered.contains("\x1b[31m^\x1b[0m"));
+    }
+}
@@ -0,0 +1,250 @@
+//! Renders [`Bytecode`] for reading: every function's instructions with
+//! their offsets, decoded operands and source lines, and the constant pool.
+//!
+//! ```text
+//! == main ==
+//!    1 | let x = 1 + y;
+//!        0000 OpConstant 0         ; 1
+//!        0003 OpGetGlobal 0        ; y
+//! ```
+
+use std::fmt::Write;
+
+use crate::{
+    code::{format_instruction, Opcode},
+    compiler::Bytecode,
+    object::{CompiledFunction, Object},
+};
+
+// How much of a function's source to show next to the instructions that
+// refer to it.
+const MAX_SUMMARY: usize = 40;
+
+pub struct Disassembler<'a> {
+    bytecode: &'a Bytecode,
+    source: Option<&'a str>,
+}
+
+impl<'a> Disassembler<'a> {
+    pub fn new(bytecode: &'a Bytecode) -> Disassembler<'a> {
+        Disassembler {
+            bytecode,
+            source: None,
+        }
+    }
+
+    /// Shows each source line above the instructions compiled from it;
+    /// without a source only the line numbers are shown.
+    pub fn with_source(mut self, source: &'a str) -> Disassembler<'a> {
+        self.source = Some(source);
+        self
+    }
+
+    /// Lists the top-level code, then each function after the function
+    /// that creates it, then the constant pool.
+    pub fn disassemble(&self) -> String {
+        let mut out = String::from("== main ==\n");
+        self.function(&mut out, &self.bytecode.main, &mut vec![]);
+
+        out.push_str("\n== constants ==\n");
+        for (index, constant) in self.bytecode.constants.iter().enumerate() {
+            let _ = writeln!(
+                out,
+                "{:04} {} {}",
+                index,
+                constant.type_name(),
+                summary(constant)
+            );
+        }
+        out
+    }
+
+    // `enclosing` holds the functions around `function`, innermost last,
+    // to name the locals that `OpGetOuter` reads.
+    fn function<'f>(
+        &'f self,
+        out: &mut String,
+        function: &'f CompiledFunction,
+        enclosing: &mut Vec<&'f CompiledFunction>,
+    ) {
+        let instructions = &function.instructions;
+        let mut nested = vec![];
+        let mut line = None;
+        let mut offset = 0;
+
+        while offset < instructions.len() {
+            let (op, operands) = match instructions.instruction_at(offset) {
+                Ok(decoded) => decoded,
+                Err(err) => {
+                    let _ = writeln!(out, "       {:04} ERROR: {}", offset, err);
+                    break;
+                }
+            };
+
+            let next_line = function.line(offset);
+            let gutter = match next_line {
+                Some(n) if next_line != line => {
+                    line = next_line;
+                    match self.source.and_then(|source| source.lines().nth(n - 1)) {
+                        Some(text) => {
+                            let _ = writeln!(out, "{:>4} | {}", n, text.trim_end());
+                            String::from("    ")
+                        }
+                        None => format!("{:>4}", n),
+                    }
+                }
+                _ => String::from("    "),
+            };
+
+            let instruction = format_instruction(op, &operands);
+            match self.comment(function, enclosing, op, &operands) {
+                Some(comment) => {
+                    let _ = writeln!(
+                        out,
+                        "{}   {:04} {:<20} ; {}",
+                        gutter, offset, instruction, comment
+                    );
+                }
+                None => {
+                    let _ = writeln!(out, "{}   {:04} {}", gutter, offset, instruction);
+                }
+            }
+
+            if op == Opcode::Closure {
+                nested.push(operands[0]);
+            }
+            offset += op.width();
+        }
+
+        enclosing.push(function);
+        for index in nested {
+            if let Some(Object::CompiledFunction(inner)) = self.bytecode.constants.get(index) {
+                let _ = writeln!(out, "\n== constant {}: {} ==", index, summary_of(inner));
+                if !inner.locals.is_empty() {
+                    let _ = writeln!(out, "locals: {}", inner.locals.join(", "));
+                }
+                self.function(out, inner, enclosing);
+            }
+        }
+        enclosing.pop();
+    }
+
+    /// Explains the operands of an instruction: the constant, variable or
+    /// function that they refer to.
+    fn comment(
+        &self,
+        function: &CompiledFunction,
+        enclosing: &[&CompiledFunction],
+        op: Opcode,
+        operands: &[usize],
+    ) -> Option<String> {
+        match op {
+            Opcode::Constant | Opcode::Closure => {
+                self.bytecode.constants.get(operands[0]).map(summary)
+            }
+            Opcode::GetGlobal | Opcode::SetGlobal => {
+                self.bytecode.globals.get(operands[0]).cloned()
+            }
+            Opcode::GetLocal | Opcode::SetLocal => function.locals.get(operands[0]).cloned(),
+            Opcode::GetOuter => {
+                let outer = enclosing.len().checked_sub(operands[0])?;
+                enclosing.get(outer)?.locals.get(operands[1]).cloned()
+            }
+            _ => None,
+        }
+    }
+}
+
+fn summary(constant: &Object) -> String {
+    match constant {
+        Object::String(value) => format!("{:?}", value),
+        Object::CompiledFunction(function) => summary_of(function),
+        obj => obj.inspect(),
+    }
+}
+
+fn summary_of(function: &CompiledFunction) -> String {
+    if function.source.chars().count() <= MAX_SUMMARY {
+        return function.source.clone();
+    }
+    let head: String = function.source.chars().take(MAX_SUMMARY - 3).collect();
+    format!("{}...", head)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compiler::{Bytecode, Compiler};
+    use crate::disasm::Disassembler;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    fn compile(input: &str) -> Bytecode {
+        let mut p = Parser::new(Lexer::new(input.to_string()));
+        let program = p.parse_program();
+        assert!(p.errors.is_empty(), "parser errors: {:?}", p.errors);
+        Compiler::new().compile(&program).unwrap()
+    }
+
+    #[test]
+    fn test_disassemble() {
+        let input = "let s = \"a\";\nlet f = fn(x) {\n  fn() { x + s }\n};";
+        let bytecode = compile(input);
+
+        let expected = "\
+== main ==
+   1   0000 OpConstant 0         ; \"a\"
+       0003 OpSetGlobal 0        ; s
+   2   0006 OpClosure 2          ; fn(x) { fn() { (x + s); }; }
+       0009 OpSetGlobal 1        ; f
+       0012 OpNull
+       0013 OpReturnValue
+
+== constant 2: fn(x) { fn() { (x + s); }; } ==
+locals: x
+   3   0000 OpClosure 1          ; fn() { (x + s); }
+   2   0003 OpReturnValue
+
+== constant 1: fn() { (x + s); } ==
+   3   0000 OpGetOuter 1 0       ; x
+       0004 OpGetGlobal 0        ; s
+       0007 OpAdd
+       0008 OpReturnValue
+
+== constants ==
+0000 STRING \"a\"
+0001 COMPILED_FUNCTION fn() { (x + s); }
+0002 COMPILED_FUNCTION fn(x) { fn() { (x + s); }; }
+";
+        assert_eq!(Disassembler::new(&bytecode).disassemble(), expected);
+    }
+
+    #[test]
+    fn test_disassemble_with_source() {
+        let input = "let x = 1;\nputs(x +\n  2);";
+        let bytecode = compile(input);
+
+        let expected = "\
+== main ==
+   1 | let x = 1;
+       0000 OpConstant 0         ; 1
+       0003 OpSetGlobal 0        ; x
+   2 | puts(x +
+       0006 OpGetGlobal 1        ; puts
+       0009 OpGetGlobal 0        ; x
+       0012 OpConstant 1         ; 2
+       0015 OpAdd
+       0016 OpCall 1
+       0018 OpReturnValue
+
+== constants ==
+0000 INTEGER 1
+0001 INTEGER 2
+";
+        assert_eq!(
+            Disassembler::new(&bytecode)
+                .with_source(input)
+                .disassemble(),
+            expected
+        );
+    }
+}
@@ -0,0 +1,209 @@
+//! Reading REPL input: with line editing, history and completion on a
+//! capable terminal, and plain lines from standard input everywhere else.
+
+use std::{
+    env,
+    io::{self, BufRead, IsTerminal, Write},
+    path::PathBuf,
+};
+
+use rustyline::{
+    completion::Completer, config::Config, error::ReadlineError, highlight::Highlighter,
+    hint::Hinter, history::FileHistory, validate::Validator, CompletionType, Context, Editor,
+    Helper,
+};
+
+const HISTORY_FILE: &str = ".monkey_history";
+
+/// What reading one line produced.
+pub enum Line {
+    /// A line of input, ending with a newline unless it was the last line
+    /// of a file.
+    Text(String),
+    /// Ctrl-C: the user abandoned the input being typed.
+    Interrupted,
+    /// End of input.
+    Eof,
+}
+
+pub enum LineReader {
+    /// A line editor on a terminal. The history is saved to `history`
+    /// when there is somewhere to save it.
+    Editor {
+        editor: Box<Editor<Completions, FileHistory>>,
+        history: Option<PathBuf>,
+    },
+    /// Plain reads from standard input, for pipes and dumb terminals.
+    Plain,
+}
+
+impl LineReader {
+    /// Edits lines when both standard input and output are terminals and
+    /// `TERM` is not `dumb`, loading the history from `~/.monkey_history`.
+    /// Falls back to plain reads otherwise, or when the terminal cannot be
+    /// set up.
+    pub fn new() -> LineReader {
+        let dumb = env::var_os("TERM").is_none_or(|term| term == "dumb");
+        if dumb || !io::stdin().is_terminal() || !io::stdout().is_terminal() {
+            return LineReader::Plain;
+        }
+
+        let config = Config::builder()
+            .auto_add_history(false)
+            .completion_type(CompletionType::List)
+            .build();
+        let mut editor = match Editor::with_config(config) {
+            Ok(editor) => editor,
+            Err(_) => return LineReader::Plain,
+        };
+        editor.set_helper(Some(Completions::default()));
+
+        let history = env::var_os("HOME").map(|home| PathBuf::from(home).join(HISTORY_FILE));
+        if let Some(path) = &history {
+            // A missing file just means a first session.
+            let _ = editor.load_history(path);
+        }
+        LineReader::Editor {
+            editor: Box::new(editor),
+            history,
+        }
+    }
+
+    /// Prints `prompt` and reads a line.
+    pub fn read_line(&mut self, prompt: &str) -> io::Result<Line> {
+        match self {
+            LineReader::Editor { editor, .. } => match editor.readline(prompt) {
+                Ok(mut line) => {
+                    line.push('\n');
+                    Ok(Line::Text(line))
+                }
+                Err(ReadlineError::Interrupted) => Ok(Line::Interrupted),
+                Err(ReadlineError::Eof) => Ok(Line::Eof),
+                Err(ReadlineError::Io(err)) => Err(err),
+                Err(err) => Err(io::Error::other(err.to_string())),
+            },
+            LineReader::Plain => {
+                print!("{}", prompt);
+                io::stdout().flush()?;
+                let mut line = String::new();
+                match io::stdin().lock().read_line(&mut line)? {
+                    0 => Ok(Line::Eof),
+                    _ => Ok(Line::Text(line)),
+                }
+            }
+        }
+    }
+
+    /// Records a complete input, possibly several lines long, so that it
+    /// can be recalled with the arrow keys or Ctrl-R.
+    pub fn add_history(&mut self, input: &str) {
+        if let LineReader::Editor { editor, .. } = self {
+            let _ = editor.add_history_entry(input.trim_end());
+        }
+    }
+
+    /// Replaces the words offered by tab completion.
+    pub fn set_completions(&mut self, words: Vec<String>) {
+        if let LineReader::Editor { editor, .. } = self {
+            if let Some(helper) = editor.helper_mut() {
+                helper.words = words;
+            }
+        }
+    }
+
+    /// Writes the history back to its file.
+    pub fn save_history(&mut self) -> Result<(), ReadlineError> {
+        match self {
+            LineReader::Editor {
+                editor,
+                history: Some(path),
+            } => editor.save_history(path),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Completes the identifier before the cursor from a list of words.
+#[derive(Default)]
+pub struct Completions {
+    words: Vec<String>,
+}
+
+impl Completer for Completions {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        Ok(complete(&self.words, line, pos))
+    }
+}
+
+impl Hinter for Completions {
+    type Hint = String;
+}
+
+impl Highlighter for Completions {}
+
+impl Validator for Completions {}
+
+impl Helper for Completions {}
+
+/// Returns where the identifier ending at byte `pos` of `line` starts,
+/// and the words that it is a prefix of.
+fn complete(words: &[String], line: &str, pos: usize) -> (usize, Vec<String>) {
+    let before = &line[..pos];
+    let start = before
+        .char_indices()
+        .rev()
+        .take_while(|&(_, c)| c == '_' || unicode_ident::is_xid_continue(c))
+        .last()
+        .map_or(pos, |(i, _)| i);
+    let prefix = &before[start..];
+
+    // Numbers are not identifiers, so there is nothing to complete.
+    if prefix.starts_with(|c: char| c.is_ascii_digit()) {
+        return (pos, vec![]);
+    }
+    let candidates = words
+        .iter()
+        .filter(|word| word.starts_with(prefix))
+        .cloned()
+        .collect();
+    (start, candidates)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::editor::complete;
+
+    #[test]
+    fn test_complete() {
+        let words: Vec<String> = ["fn", "false", "first", "foo_bar", "len", "let", "größe"]
+            .iter()
+            .map(|word| word.to_string())
+            .collect();
+
+        let tests = vec![
+            ("f", 1, (0, vec!["fn", "false", "first", "foo_bar"])),
+            ("let x = fi", 10, (8, vec!["first"])),
+            ("puts(foo_", 9, (5, vec!["foo_bar"])),
+            ("le + 1", 2, (0, vec!["len", "let"])),
+            ("x + grö", 8, (4, vec!["größe"])),
+            ("let x = 12", 10, (10, vec![])),
+            ("xyz", 3, (0, vec![])),
+        ];
+
+        for (line, pos, (start, candidates)) in tests {
+            assert_eq!(
+                complete(&words, line, pos),
+                (start, candidates.iter().map(|c| c.to_string()).collect()),
+                "{:?}",
+                line
+            );
+        }
+    }
+}
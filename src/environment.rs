@@ -0,0 +1,128 @@
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    fmt,
+    rc::Rc,
+};
+
+use crate::{
+    builtins::Builtins,
+    object::{Arity, Object},
+};
+
+/// A scope of bindings. Cloning an `Environment` yields another handle to the
+/// same scope, which is how function objects capture the scope they were
+/// defined in. Every scope shares the builtins of the outermost one, and
+/// its count of the function calls under way.
+#[derive(Clone)]
+pub struct Environment {
+    store: Rc<RefCell<HashMap<String, Object>>>,
+    outer: Option<Box<Environment>>,
+    builtins: Rc<RefCell<Builtins>>,
+    calls: Rc<Cell<usize>>,
+}
+
+impl Environment {
+    pub fn new() -> Environment {
+        Environment::with_builtins(Builtins::standard())
+    }
+
+    pub fn with_builtins(builtins: Builtins) -> Environment {
+        Environment {
+            store: Rc::new(RefCell::new(HashMap::new())),
+            outer: None,
+            builtins: Rc::new(RefCell::new(builtins)),
+            calls: Rc::new(Cell::new(0)),
+        }
+    }
+
+    pub fn new_enclosed(outer: &Environment) -> Environment {
+        Environment {
+            store: Rc::new(RefCell::new(HashMap::new())),
+            outer: Some(Box::new(outer.clone())),
+            builtins: Rc::clone(&outer.builtins),
+            calls: Rc::clone(&outer.calls),
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<Object> {
+        match self.store.borrow().get(name) {
+            Some(val) => Some(val.clone()),
+            None => self.outer.as_ref().and_then(|outer| outer.get(name)),
+        }
+    }
+
+    /// Returns the bindings made in this scope, not its outer ones, sorted
+    /// by name.
+    pub fn bindings(&self) -> Vec<(String, Object)> {
+        let mut bindings: Vec<(String, Object)> = self
+            .store
+            .borrow()
+            .iter()
+            .map(|(name, val)| (name.clone(), val.clone()))
+            .collect();
+        bindings.sort_by(|a, b| a.0.cmp(&b.0));
+        bindings
+    }
+
+    pub fn set(&mut self, name: &str, val: Object) -> Object {
+        self.store
+            .borrow_mut()
+            .insert(name.to_string(), val.clone());
+        val
+    }
+
+    /// Makes a native function callable by name from Monkey code evaluated
+    /// in this environment; see [`Builtins::register`].
+    pub fn register_builtin<F>(&mut self, name: &str, arity: Arity, func: F)
+    where
+        F: Fn(&[Object]) -> Object + 'static,
+    {
+        self.builtins.borrow_mut().register(name, arity, func);
+    }
+
+    pub fn builtin(&self, name: &str) -> Option<Object> {
+        self.builtins.borrow().lookup(name)
+    }
+
+    /// Returns the names of the builtins, sorted.
+    pub fn builtin_names(&self) -> Vec<String> {
+        self.builtins.borrow().names().map(String::from).collect()
+    }
+
+    /// Counts a function call as under way, returning how many now are.
+    pub(crate) fn enter_call(&self) -> usize {
+        self.calls.set(self.calls.get() + 1);
+        self.calls.get()
+    }
+
+    pub(crate) fn leave_call(&self) {
+        self.calls.set(self.calls.get() - 1);
+    }
+}
+
+impl Default for Environment {
+    fn default() -> Environment {
+        Environment::new()
+    }
+}
+
+impl PartialEq for Environment {
+    fn eq(&self, other: &Environment) -> bool {
+        Rc::ptr_eq(&self.store, &other.store)
+    }
+}
+
+impl fmt::Debug for Environment {
+    // Only the names are printed: a function stored in its own defining
+    // scope would otherwise recurse forever.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let store = self.store.borrow();
+        let mut names: Vec<&String> = store.keys().collect();
+        names.sort();
+        f.debug_struct("Environment")
+            .field("names", &names)
+            .field("outer", &self.outer)
+            .finish()
+    }
+}
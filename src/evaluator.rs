@@ -0,0 +1,940 @@
+use std::{collections::BTreeMap, rc::Rc};
+
+use crate::{
+    ast::{BlockStatement, Expression, HashLiteral, IfExpression, Program, Statement},
+    environment::Environment,
+    object::{Function, HashPair, Object, FALSE, NULL, TRUE},
+};
+
+/// How deeply function calls may nest before evaluation stops with a
+/// stack overflow error. Each call takes a few kilobytes of native stack,
+/// more in debug builds, so the thread running the evaluator needs a stack
+/// far larger than the default to reach it.
+pub const MAX_CALL_DEPTH: usize = 1 << 14;
+
+pub fn eval(program: &Program, env: &mut Environment) -> Object {
+    let mut result = NULL;
+
+    for stmt in program.statements.iter() {
+        result = eval_statement(stmt, env);
+
+        match result {
+            Object::ReturnValue(value) => return *value,
+            Object::Error(_) => return result,
+            _ => (),
+        }
+    }
+
+    result
+}
+
+fn eval_block_statement(block: &BlockStatement, env: &mut Environment) -> Object {
+    let mut result = NULL;
+
+    for stmt in block.statements.iter() {
+        result = eval_statement(stmt, env);
+
+        // Leave the return value wrapped so the enclosing program (or
+        // function) knows to stop evaluating as well.
+        if let Object::ReturnValue(_) | Object::Error(_) = result {
+            return result;
+        }
+    }
+
+    result
+}
+
+fn eval_statement(stmt: &Statement, env: &mut Environment) -> Object {
+    match stmt {
+        Statement::Expression(s) => eval_expression(&s.expression, env),
+        Statement::Return(s) => {
+            let val = eval_expression(&s.return_value, env);
+            if val.is_error_or_return() {
+                return val;
+            }
+            Object::ReturnValue(Box::new(val))
+        }
+        Statement::Let(s) => {
+            let val = eval_expression(&s.value, env);
+            if val.is_error_or_return() {
+                return val;
+            }
+            env.set(&s.name.value, val);
+            NULL
+        }
+        Statement::Error(s) => new_error(format!("syntax error at {}", s.span)),
+    }
+}
+
+fn eval_expression(exp: &Expression, env: &mut Environment) -> Object {
+    match exp {
+        Expression::IntegerLiteral(e) => Object::Integer(e.value),
+        Expression::StringLiteral(e) => Object::String(e.value.clone()),
+        Expression::Boolean(e) => native_bool_to_boolean_object(e.value),
+        Expression::Identifier(e) => match env.get(&e.value).or_else(|| env.builtin(&e.value)) {
+            Some(val) => val,
+            None => new_error(format!("identifier not found: {}", e.value)),
+        },
+        Expression::Prefix(e) => {
+            let right = eval_expression(&e.right, env);
+            if right.is_error_or_return() {
+                return right;
+            }
+            eval_prefix_expression(&e.operator, right)
+        }
+        Expression::Infix(e) => {
+            let left = eval_expression(&e.left, env);
+            if left.is_error_or_return() {
+                return left;
+            }
+            let right = eval_expression(&e.right, env);
+            if right.is_error_or_return() {
+                return right;
+            }
+            eval_infix_expression(&e.operator, left, right)
+        }
+        Expression::If(e) => eval_if_expression(e, env),
+        Expression::Function(e) => Object::Function(Rc::new(Function {
+            parameters: e.parameters.clone(),
+            body: e.body.clone(),
+            env: env.clone(),
+        })),
+        Expression::Call(e) => {
+            let function = eval_expression(&e.function, env);
+            if function.is_error_or_return() {
+                return function;
+            }
+            let args = match eval_expressions(&e.arguments, env) {
+                Ok(args) => args,
+                Err(err) => return err,
+            };
+            apply_function(function, args, env)
+        }
+        Expression::Array(e) => match eval_expressions(&e.elements, env) {
+            Ok(elements) => Object::Array(elements),
+            Err(err) => err,
+        },
+        Expression::Index(e) => {
+            let left = eval_expression(&e.left, env);
+            if left.is_error_or_return() {
+                return left;
+            }
+            let index = eval_expression(&e.index, env);
+            if index.is_error_or_return() {
+                return index;
+            }
+            eval_index_expression(left, index)
+        }
+        Expression::Hash(e) => eval_hash_literal(e, env),
+    }
+}
+
+fn eval_expressions(exps: &[Expression], env: &mut Environment) -> Result<Vec<Object>, Object> {
+    let mut result = vec![];
+
+    for e in exps.iter() {
+        let evaluated = eval_expression(e, env);
+        if evaluated.is_error_or_return() {
+            return Err(evaluated);
+        }
+        result.push(evaluated);
+    }
+
+    Ok(result)
+}
+
+fn apply_function(function: Object, args: Vec<Object>, env: &Environment) -> Object {
+    match function {
+        Object::Function(function) => {
+            if function.parameters.len() != args.len() {
+                return new_error(format!(
+                    "wrong number of arguments: want={}, got={}",
+                    function.parameters.len(),
+                    args.len()
+                ));
+            }
+            if env.enter_call() > MAX_CALL_DEPTH {
+                env.leave_call();
+                return new_error(String::from("stack overflow"));
+            }
+
+            let mut extended_env = extend_function_env(&function, args);
+            let evaluated = eval_block_statement(&function.body, &mut extended_env);
+            env.leave_call();
+            unwrap_return_value(evaluated)
+        }
+        Object::Builtin(builtin) => builtin.call(&args),
+        _ => new_error(format!("not a function: {}", function.type_name())),
+    }
+}
+
+fn extend_function_env(function: &Function, args: Vec<Object>) -> Environment {
+    let mut env = Environment::new_enclosed(&function.env);
+
+    for (param, arg) in function.parameters.iter().zip(args) {
+        env.set(&param.value, arg);
+    }
+
+    env
+}
+
+fn unwrap_return_value(obj: Object) -> Object {
+    match obj {
+        Object::ReturnValue(value) => *value,
+        _ => obj,
+    }
+}
+
+pub(crate) fn eval_prefix_expression(operator: &str, right: Object) -> Object {
+    match operator {
+        "!" => eval_bang_operator_expression(right),
+        "-" => eval_minus_prefix_operator_expression(right),
+        _ => new_error(format!(
+            "unknown operator: {}{}",
+            operator,
+            right.type_name()
+        )),
+    }
+}
+
+fn eval_bang_operator_expression(right: Object) -> Object {
+    native_bool_to_boolean_object(!is_truthy(&right))
+}
+
+fn eval_minus_prefix_operator_expression(right: Object) -> Object {
+    match right {
+        Object::Integer(value) => Object::Integer(value.wrapping_neg()),
+        _ => new_error(format!("unknown operator: -{}", right.type_name())),
+    }
+}
+
+pub(crate) fn eval_infix_expression(operator: &str, left: Object, right: Object) -> Object {
+    match (&left, &right) {
+        (Object::Integer(l), Object::Integer(r)) => eval_integer_infix_expression(operator, *l, *r),
+        (Object::String(l), Object::String(r)) => eval_string_infix_expression(operator, l, r),
+        (Object::Boolean(l), Object::Boolean(r)) if operator == "==" => {
+            native_bool_to_boolean_object(l == r)
+        }
+        (Object::Boolean(l), Object::Boolean(r)) if operator == "!=" => {
+            native_bool_to_boolean_object(l != r)
+        }
+        _ if left.type_name() != right.type_name() => new_error(format!(
+            "type mismatch: {} {} {}",
+            left.type_name(),
+            operator,
+            right.type_name()
+        )),
+        _ => new_error(format!(
+            "unknown operator: {} {} {}",
+            left.type_name(),
+            operator,
+            right.type_name()
+        )),
+    }
+}
+
+fn eval_integer_infix_expression(operator: &str, left: i64, right: i64) -> Object {
+    match operator {
+        "+" => Object::Integer(left.wrapping_add(right)),
+        "-" => Object::Integer(left.wrapping_sub(right)),
+        "*" => Object::Integer(left.wrapping_mul(right)),
+        "/" => {
+            if right == 0 {
+                return new_error(String::from("division by zero"));
+            }
+            Object::Integer(left.wrapping_div(right))
+        }
+        "<" => native_bool_to_boolean_object(left < right),
+        ">" => native_bool_to_boolean_object(left > right),
+        "==" => native_bool_to_boolean_object(left == right),
+        "!=" => native_bool_to_boolean_object(left != right),
+        _ => new_error(format!("unknown operator: INTEGER {} INTEGER", operator)),
+    }
+}
+
+fn eval_string_infix_expression(operator: &str, left: &str, right: &str) -> Object {
+    match operator {
+        "+" => Object::String(format!("{}{}", left, right)),
+        "==" => native_bool_to_boolean_object(left == right),
+        "!=" => native_bool_to_boolean_object(left != right),
+        _ => new_error(format!("unknown operator: STRING {} STRING", operator)),
+    }
+}
+
+fn eval_hash_literal(node: &HashLiteral, env: &mut Environment) -> Object {
+    let mut pairs = BTreeMap::new();
+
+    for (key_node, value_node) in node.pairs.iter() {
+        let key = eval_expression(key_node, env);
+        if key.is_error_or_return() {
+            return key;
+        }
+
+        let hash_key = match key.hash_key() {
+            Some(hash_key) => hash_key,
+            None => return new_error(format!("unusable as hash key: {}", key.type_name())),
+        };
+
+        let value = eval_expression(value_node, env);
+        if value.is_error_or_return() {
+            return value;
+        }
+
+        pairs.insert(hash_key, HashPair { key, value });
+    }
+
+    Object::Hash(pairs)
+}
+
+pub(crate) fn eval_index_expression(left: Object, index: Object) -> Object {
+    match (&left, &index) {
+        (Object::Hash(pairs), _) => match index.hash_key() {
+            Some(hash_key) => pairs
+                .get(&hash_key)
+                .map(|pair| pair.value.clone())
+                .unwrap_or(NULL),
+            None => new_error(format!("unusable as hash key: {}", index.type_name())),
+        },
+        (Object::Array(elements), Object::Integer(i)) => usize::try_from(*i)
+            .ok()
+            .and_then(|i| elements.get(i))
+            .cloned()
+            .unwrap_or(NULL),
+        _ => new_error(format!(
+            "index operator not supported: {}",
+            left.type_name()
+        )),
+    }
+}
+
+fn eval_if_expression(ie: &IfExpression, env: &mut Environment) -> Object {
+    let condition = eval_expression(&ie.condition, env);
+    if condition.is_error_or_return() {
+        return condition;
+    }
+
+    if is_truthy(&condition) {
+        eval_block_statement(&ie.consequence, env)
+    } else if let Some(alt) = &ie.alternative {
+        eval_block_statement(alt, env)
+    } else {
+        NULL
+    }
+}
+
+pub(crate) fn is_truthy(obj: &Object) -> bool {
+    !matches!(obj, Object::Null | Object::Boolean(false))
+}
+
+fn native_bool_to_boolean_object(input: bool) -> Object {
+    if input {
+        TRUE
+    } else {
+        FALSE
+    }
+}
+
+fn new_error(message: String) -> Object {
+    Object::Error(message)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ast::Node;
+    use crate::environment::Environment;
+    use crate::evaluator::eval;
+    use crate::lexer::Lexer;
+    use crate::object::{HashKey, Object};
+    use crate::parser::Parser;
+
+    fn test_eval(input: &str) -> Object {
+        let lexer = Lexer::new(String::from(input));
+        let mut p = Parser::new(lexer);
+        let program = p.parse_program();
+        let mut env = Environment::new();
+
+        eval(&program, &mut env)
+    }
+
+    #[test]
+    fn test_eval_integer_expression() {
+        let tests = vec![
+            ("5", 5),
+            ("10", 10),
+            ("-5", -5),
+            ("-10", -10),
+            ("5 + 5 + 5 + 5 - 10", 10),
+            ("2 * 2 * 2 * 2 * 2", 32),
+            ("-50 + 100 + -50", 0),
+            ("5 * 2 + 10", 20),
+            ("5 + 2 * 10", 25),
+            ("20 + 2 * -10", 0),
+            ("50 / 2 * 2 + 10", 60),
+            ("2 * (5 + 10)", 30),
+            ("3 * 3 * 3 + 10", 37),
+            ("3 * (3 * 3) + 10", 37),
+            ("(5 + 10 * 2 + 15 / 3) * 2 + -10", 50),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(test_eval(input), Object::Integer(expected), "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_eval_boolean_expression() {
+        let tests = vec![
+            ("true", true),
+            ("false", false),
+            ("1 < 2", true),
+            ("1 > 2", false),
+            ("1 < 1", false),
+            ("1 == 1", true),
+            ("1 != 1", false),
+            ("1 != 2", true),
+            ("true == true", true),
+            ("false == false", true),
+            ("true != false", true),
+            ("(1 < 2) == true", true),
+            ("(1 > 2) == true", false),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(test_eval(input), Object::Boolean(expected), "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_bang_operator() {
+        let tests = vec![
+            ("!true", false),
+            ("!false", true),
+            ("!5", false),
+            ("!!true", true),
+            ("!!false", false),
+            ("!!5", true),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(test_eval(input), Object::Boolean(expected), "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_if_else_expressions() {
+        let tests = vec![
+            ("if (true) { 10 }", Object::Integer(10)),
+            ("if (false) { 10 }", Object::Null),
+            ("if (1) { 10 }", Object::Integer(10)),
+            ("if (1 < 2) { 10 }", Object::Integer(10)),
+            ("if (1 > 2) { 10 }", Object::Null),
+            ("if (1 > 2) { 10 } else { 20 }", Object::Integer(20)),
+            ("if (1 < 2) { 10 } else { 20 }", Object::Integer(10)),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(test_eval(input), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_return_statements() {
+        let tests = vec![
+            ("return 10;", 10),
+            ("return 10; 9;", 10),
+            ("return 2 * 5; 9;", 10),
+            ("9; return 2 * 5; 9;", 10),
+            ("if (10 > 1) { if (10 > 1) { return 10; } return 1; }", 10),
+            // A return nested in an expression leaves the whole program.
+            ("let x = if (true) { return 10; }; 2", 10),
+            ("-if (true) { return 10; }; 2", 10),
+            ("(if (true) { return 10; }) + 1; 2", 10),
+            ("1 + if (true) { return 10; }; 2", 10),
+            ("[1, if (true) { return 10; }, 3]; 2", 10),
+            ("{if (true) { return 10; }: 1}; 2", 10),
+            ("{1: if (true) { return 10; }}; 2", 10),
+            ("[1][if (true) { return 10; }]; 2", 10),
+            ("len(if (true) { return 10; }); 2", 10),
+            ("if (if (true) { return 10; }) { 1 }; 2", 10),
+            ("return if (true) { return 10; }; 2", 10),
+            (
+                "let f = fn() { let x = if (true) { return 10; }; 2 }; f()",
+                10,
+            ),
+            ("let f = fn() { [if (true) { return 10; }, 5] }; f()", 10),
+            ("let f = fn() { (if (true) { return 10; }) + 1 }; f()", 10),
+            ("let f = fn(x) { x }; f(if (true) { return 10; }); 2", 10),
+            (
+                "let f = fn() { puts(if (true) { return 10; }); 2 }; f() * 1",
+                10,
+            ),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(test_eval(input), Object::Integer(expected), "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_error_handling() {
+        let tests = vec![
+            ("5 + true;", "type mismatch: INTEGER + BOOLEAN"),
+            ("5 + true; 5;", "type mismatch: INTEGER + BOOLEAN"),
+            ("-true", "unknown operator: -BOOLEAN"),
+            ("true + false;", "unknown operator: BOOLEAN + BOOLEAN"),
+            ("5; true + false; 5", "unknown operator: BOOLEAN + BOOLEAN"),
+            (
+                "if (10 > 1) { true + false; }",
+                "unknown operator: BOOLEAN + BOOLEAN",
+            ),
+            (
+                "if (10 > 1) { if (10 > 1) { return true + false; } return 1; }",
+                "unknown operator: BOOLEAN + BOOLEAN",
+            ),
+            ("foobar", "identifier not found: foobar"),
+            ("10 / (5 - 5)", "division by zero"),
+            (r#""Hello" - "World""#, "unknown operator: STRING - STRING"),
+            (r#""a" + 1"#, "type mismatch: STRING + INTEGER"),
+            ("let a = 1; let = 2; a", "syntax error at 1:12"),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(
+                test_eval(input),
+                Object::Error(String::from(expected)),
+                "{}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn test_function_object() {
+        match test_eval("fn(x) { x + 2; };") {
+            Object::Function(function) => {
+                assert_eq!(function.parameters.len(), 1);
+                assert_eq!(function.parameters[0].value, "x");
+                assert_eq!(function.body.string(), "{ (x + 2); }");
+            }
+            obj => panic!("expected function, got {:?}", obj),
+        }
+    }
+
+    #[test]
+    fn test_function_application() {
+        let tests = vec![
+            ("let identity = fn(x) { x; }; identity(5);", 5),
+            ("let identity = fn(x) { return x; }; identity(5);", 5),
+            ("let double = fn(x) { x * 2; }; double(5);", 10),
+            ("let add = fn(x, y) { x + y; }; add(5, 5);", 10),
+            ("let add = fn(x, y) { x + y; }; add(5 + 5, add(5, 5));", 20),
+            ("fn(x) { x; }(5)", 5),
+            ("let f = fn() { return 1; 2; }; f() + 10;", 11),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(test_eval(input), Object::Integer(expected), "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_closures() {
+        let tests = vec![
+            (
+                "let newAdder = fn(x) { fn(y) { x + y }; };
+                let addTwo = newAdder(2);
+                addTwo(2);",
+                4,
+            ),
+            (
+                "let x = 10;
+                let shadow = fn(x) { x * 2 };
+                shadow(3) + x;",
+                16,
+            ),
+            (
+                "let compose = fn(f, g) { fn(x) { g(f(x)) } };
+                let inc = fn(x) { x + 1 };
+                let double = fn(x) { x * 2 };
+                compose(inc, double)(5);",
+                12,
+            ),
+            (
+                "let reduce = fn(f, acc, n) {
+                    if (n == 0) { return acc; }
+                    reduce(f, f(acc, n), n - 1);
+                };
+                reduce(fn(a, b) { a + b }, 0, 10);",
+                55,
+            ),
+            (
+                "let fib = fn(n) { if (n < 2) { n } else { fib(n - 1) + fib(n - 2) } };
+                fib(15);",
+                610,
+            ),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(test_eval(input), Object::Integer(expected), "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_function_call_errors() {
+        let tests = vec![
+            (
+                "let f = fn(x, y) { x }; f(1);",
+                "wrong number of arguments: want=2, got=1",
+            ),
+            ("let x = 5; x(1);", "not a function: INTEGER"),
+            ("let f = fn() { y }; f();", "identifier not found: y"),
+            ("let f = fn(x) { x }; f(foo);", "identifier not found: foo"),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(
+                test_eval(input),
+                Object::Error(String::from(expected)),
+                "{}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn test_call_depth() {
+        // Like the `monkey` binary, run where there is room for the calls.
+        let deep = std::thread::Builder::new().stack_size(1 << 30);
+        let evaluator = deep.spawn(|| {
+            let overflow = Object::Error(String::from("stack overflow"));
+            let tests = vec![
+                ("let f = fn() { f() }; f()", overflow.clone()),
+                ("let f = fn(n) { 1 + f(n + 1) }; f(0); 5", overflow),
+                (
+                    "let sum = fn(n) { if (n == 0) { 0 } else { n + sum(n - 1) } }; sum(10000)",
+                    Object::Integer(50005000),
+                ),
+                // The depth goes back down as calls return.
+                (
+                    "let f = fn(n) { if (n > 0) { f(n - 1) } }; f(16000); f(16000); 1",
+                    Object::Integer(1),
+                ),
+            ];
+
+            for (input, expected) in tests {
+                assert_eq!(test_eval(input), expected, "{}", input);
+            }
+        });
+        evaluator.unwrap().join().unwrap();
+    }
+
+    #[test]
+    fn test_string_literal() {
+        assert_eq!(
+            test_eval(r#""Hello World!""#),
+            Object::String(String::from("Hello World!"))
+        );
+    }
+
+    #[test]
+    fn test_string_concatenation() {
+        assert_eq!(
+            test_eval(r#""Hello" + " " + "World!""#),
+            Object::String(String::from("Hello World!"))
+        );
+        assert_eq!(
+            test_eval(r#"let greet = fn(name) { "hi, " + name }; greet("\u{e9}mile")"#),
+            Object::String(String::from("hi, \u{e9}mile"))
+        );
+    }
+
+    #[test]
+    fn test_string_comparison() {
+        let tests = vec![
+            (r#""a" == "a""#, true),
+            (r#""a" == "b""#, false),
+            (r#""a" != "b""#, true),
+            (r#""a\n" == "a\u{a}""#, true),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(test_eval(input), Object::Boolean(expected), "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_builtin_functions() {
+        let tests = vec![
+            (r#"len("")"#, Object::Integer(0)),
+            (r#"len("four")"#, Object::Integer(4)),
+            (r#"len("hello world")"#, Object::Integer(11)),
+            (r#"len("\u{e9}t\u{e9}")"#, Object::Integer(3)),
+            ("len([1, 2, 3])", Object::Integer(3)),
+            ("len([])", Object::Integer(0)),
+            (
+                "len(1)",
+                Object::Error(String::from("argument to `len` not supported, got INTEGER")),
+            ),
+            (
+                r#"len("one", "two")"#,
+                Object::Error(String::from("wrong number of arguments: want=1, got=2")),
+            ),
+            ("first([1, 2, 3])", Object::Integer(1)),
+            ("first([])", Object::Null),
+            (
+                "first(1)",
+                Object::Error(String::from(
+                    "argument to `first` must be ARRAY, got INTEGER",
+                )),
+            ),
+            ("last([1, 2, 3])", Object::Integer(3)),
+            ("last([])", Object::Null),
+            (
+                "last(1)",
+                Object::Error(String::from(
+                    "argument to `last` must be ARRAY, got INTEGER",
+                )),
+            ),
+            (
+                "rest([1, 2, 3])",
+                Object::Array(vec![Object::Integer(2), Object::Integer(3)]),
+            ),
+            ("rest([])", Object::Null),
+            ("push([], 1)", Object::Array(vec![Object::Integer(1)])),
+            (
+                "push(1, 1)",
+                Object::Error(String::from(
+                    "argument to `push` must be ARRAY, got INTEGER",
+                )),
+            ),
+            ("puts()", Object::Null),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(test_eval(input), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_host_registered_builtins() {
+        use std::{cell::Cell, rc::Rc};
+
+        use crate::object::Arity;
+
+        let calls = Rc::new(Cell::new(0));
+        let counter = Rc::clone(&calls);
+
+        let mut env = Environment::new();
+        env.register_builtin("add_three", Arity::Exact(3), move |args| {
+            counter.set(counter.get() + 1);
+            match args {
+                [Object::Integer(a), Object::Integer(b), Object::Integer(c)] => {
+                    Object::Integer(a + b + c)
+                }
+                _ => Object::Error(String::from("add_three expects integers")),
+            }
+        });
+        env.register_builtin("count", Arity::Variadic, |args| {
+            Object::Integer(args.len() as i64)
+        });
+
+        let tests = vec![
+            ("add_three(1, 2, 3)", Object::Integer(6)),
+            (
+                "let f = fn(g) { g(10, 20, 30) }; f(add_three)",
+                Object::Integer(60),
+            ),
+            ("count()", Object::Integer(0)),
+            ("count(1, true, \"x\")", Object::Integer(3)),
+            (
+                "add_three(1, 2)",
+                Object::Error(String::from("wrong number of arguments: want=3, got=2")),
+            ),
+            (
+                "add_three(1, 2, true)",
+                Object::Error(String::from("add_three expects integers")),
+            ),
+        ];
+
+        for (input, expected) in tests {
+            let lexer = Lexer::new(String::from(input));
+            let mut p = Parser::new(lexer);
+            let program = p.parse_program();
+            assert_eq!(eval(&program, &mut env), expected, "{}", input);
+        }
+
+        // The arity check runs before the host function is entered.
+        assert_eq!(calls.get(), 3);
+    }
+
+    #[test]
+    fn test_bindings_shadow_builtins() {
+        assert_eq!(
+            test_eval("let len = fn(x) { 42 }; len([1])"),
+            Object::Integer(42)
+        );
+        assert_eq!(
+            test_eval("let f = fn() { len([1, 2]) }; f()"),
+            Object::Integer(2)
+        );
+    }
+
+    #[test]
+    fn test_array_literals() {
+        assert_eq!(
+            test_eval("[1, 2 * 2, 3 + 3]"),
+            Object::Array(vec![
+                Object::Integer(1),
+                Object::Integer(4),
+                Object::Integer(6)
+            ])
+        );
+    }
+
+    #[test]
+    fn test_array_index_expressions() {
+        let tests = vec![
+            ("[1, 2, 3][0]", Object::Integer(1)),
+            ("[1, 2, 3][1]", Object::Integer(2)),
+            ("[1, 2, 3][2]", Object::Integer(3)),
+            ("let i = 0; [1][i];", Object::Integer(1)),
+            ("[1, 2, 3][1 + 1];", Object::Integer(3)),
+            ("let myArray = [1, 2, 3]; myArray[2];", Object::Integer(3)),
+            (
+                "let myArray = [1, 2, 3]; myArray[0] + myArray[1] + myArray[2];",
+                Object::Integer(6),
+            ),
+            (
+                "let myArray = [1, 2, 3]; let i = myArray[0]; myArray[i]",
+                Object::Integer(2),
+            ),
+            ("[1, 2, 3][3]", Object::Null),
+            ("[1, 2, 3][-1]", Object::Null),
+            (
+                "1[0]",
+                Object::Error(String::from("index operator not supported: INTEGER")),
+            ),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(test_eval(input), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_list_processing() {
+        let input = "
+            let map = fn(arr, f) {
+                let iter = fn(arr, accumulated) {
+                    if (len(arr) == 0) {
+                        accumulated
+                    } else {
+                        iter(rest(arr), push(accumulated, f(first(arr))));
+                    }
+                };
+                iter(arr, []);
+            };
+            let reduce = fn(arr, initial, f) {
+                let iter = fn(arr, result) {
+                    if (len(arr) == 0) {
+                        result
+                    } else {
+                        iter(rest(arr), f(result, first(arr)));
+                    }
+                };
+                iter(arr, initial);
+            };
+            let doubled = map([1, 2, 3, 4], fn(x) { x * 2 });
+            reduce(doubled, 0, fn(acc, x) { acc + x });
+        ";
+
+        assert_eq!(test_eval(input), Object::Integer(20));
+    }
+
+    #[test]
+    fn test_hash_literals() {
+        let input = r#"
+            let two = "two";
+            {
+                "one": 10 - 9,
+                two: 1 + 1,
+                "thr" + "ee": 6 / 2,
+                4: 4,
+                true: 5,
+                false: 6
+            }
+        "#;
+
+        let pairs = match test_eval(input) {
+            Object::Hash(pairs) => pairs,
+            obj => panic!("expected hash, got {:?}", obj),
+        };
+
+        let expected = vec![
+            (HashKey::String(String::from("one")), 1),
+            (HashKey::String(String::from("two")), 2),
+            (HashKey::String(String::from("three")), 3),
+            (HashKey::Integer(4), 4),
+            (HashKey::Boolean(true), 5),
+            (HashKey::Boolean(false), 6),
+        ];
+
+        assert_eq!(pairs.len(), expected.len());
+        for (key, value) in expected {
+            assert_eq!(pairs[&key].value, Object::Integer(value), "{:?}", key);
+        }
+    }
+
+    #[test]
+    fn test_hash_index_expressions() {
+        let tests = vec![
+            (r#"{"foo": 5}["foo"]"#, Object::Integer(5)),
+            (r#"{"foo": 5}["bar"]"#, Object::Null),
+            (r#"let key = "foo"; {"foo": 5}[key]"#, Object::Integer(5)),
+            (r#"{}["foo"]"#, Object::Null),
+            ("{5: 5}[5]", Object::Integer(5)),
+            ("{true: 5}[true]", Object::Integer(5)),
+            ("{false: 5}[false]", Object::Integer(5)),
+            ("{1: 1, 1: 2}[1]", Object::Integer(2)),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(test_eval(input), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_unhashable_keys() {
+        let tests = vec![
+            (
+                r#"{"name": "Monkey"}[fn(x) { x }];"#,
+                "unusable as hash key: FUNCTION",
+            ),
+            ("{[1]: 2}", "unusable as hash key: ARRAY"),
+            ("{{}: 2}", "unusable as hash key: HASH"),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(
+                test_eval(input),
+                Object::Error(String::from(expected)),
+                "{}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn test_let_statements() {
+        let tests = vec![
+            ("let a = 5; a;", 5),
+            ("let a = 5 * 5; a;", 25),
+            ("let a = 5; let b = a; b;", 5),
+            ("let a = 5; let b = a; let c = a + b + 5; c;", 15),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(test_eval(input), Object::Integer(expected), "{}", input);
+        }
+    }
+}
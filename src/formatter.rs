@@ -0,0 +1,686 @@
+//! Prints Monkey source in a canonical style.
+//!
+//! The formatter works on the [`cst`](crate::cst), so comments and blank
+//! lines between statements survive. Layout decisions are made by a small
+//! Wadler-style printer: each bracketed list is a group that is printed on
+//! one line when it fits in the configured width, and one item per line
+//! otherwise.
+
+use crate::{
+    cst::{self, SyntaxElement, SyntaxKind, SyntaxNode, SyntaxToken},
+    diagnostic::Diagnostic,
+    lexer::Lexer,
+    parser::Parser,
+    token::{Token, TokenKind},
+};
+
+/// Formats Monkey source:
+///
+/// ```
+/// use monkey_rs::formatter::Formatter;
+///
+/// let formatted = Formatter::new().format("let add=fn(a,b){a+b};").unwrap();
+/// assert_eq!(formatted, "let add = fn(a, b) {\n    a + b\n};\n");
+/// ```
+pub struct Formatter {
+    width: usize,
+    indent: usize,
+}
+
+impl Formatter {
+    pub fn new() -> Formatter {
+        Formatter {
+            width: 80,
+            indent: 4,
+        }
+    }
+
+    /// Sets the line width lists are broken to fit in; 80 by default.
+    pub fn with_width(mut self, width: usize) -> Formatter {
+        self.width = width;
+        self
+    }
+
+    /// Sets how many spaces each level of nesting is indented by; 4 by
+    /// default.
+    pub fn with_indent(mut self, indent: usize) -> Formatter {
+        self.indent = indent;
+        self
+    }
+
+    /// Returns the formatted source, or the parser's diagnostics if the
+    /// source does not parse. Programs with syntax errors are never
+    /// formatted, since the tree of a broken program is only a guess.
+    pub fn format(&self, source: &str) -> Result<String, Vec<Diagnostic>> {
+        let mut p = Parser::new(Lexer::new(source.to_string()));
+        p.parse_program();
+        if !p.errors.is_empty() {
+            return Err(p.errors);
+        }
+
+        let doc = program(&cst::parse(source));
+        let mut printer = Printer {
+            width: self.width,
+            indent: self.indent,
+            out: String::new(),
+            column: 0,
+            suffixes: vec![],
+        };
+        printer.print(&doc);
+        Ok(printer.finish())
+    }
+}
+
+impl Default for Formatter {
+    fn default() -> Formatter {
+        Formatter::new()
+    }
+}
+
+enum Doc {
+    Text(String),
+    // A space, or a line break when the enclosing group is broken.
+    Line,
+    // Nothing, or a line break when the enclosing group is broken.
+    SoftLine,
+    // Always a line break.
+    HardLine,
+    // A line break unless nothing has been printed on the current line.
+    FreshLine,
+    // Text held back until the end of the line, for `//` comments.
+    LineSuffix(String),
+    // Makes the enclosing group break; prints nothing.
+    BreakParent,
+    Indent(Box<Doc>),
+    Group(Box<Doc>),
+    // Laid out as in a broken group, whatever the enclosing group decided;
+    // used for block bodies, which always span lines.
+    Broken(Box<Doc>),
+    Concat(Vec<Doc>),
+}
+
+fn text(s: &str) -> Doc {
+    Doc::Text(s.to_string())
+}
+
+fn indent(doc: Doc) -> Doc {
+    Doc::Indent(Box::new(doc))
+}
+
+fn group(doc: Doc) -> Doc {
+    Doc::Group(Box::new(doc))
+}
+
+#[derive(PartialEq, Clone, Copy)]
+enum Mode {
+    Flat,
+    Break,
+}
+
+struct Printer {
+    width: usize,
+    indent: usize,
+    out: String,
+    column: usize,
+    suffixes: Vec<String>,
+}
+
+impl Printer {
+    fn print(&mut self, doc: &Doc) {
+        let mut stack: Vec<(usize, Mode, &Doc)> = vec![(0, Mode::Break, doc)];
+
+        while let Some((ind, mode, doc)) = stack.pop() {
+            match doc {
+                Doc::Text(s) => {
+                    self.out.push_str(s);
+                    self.column += s.chars().count();
+                }
+                Doc::Line if mode == Mode::Flat => {
+                    self.out.push(' ');
+                    self.column += 1;
+                }
+                Doc::SoftLine if mode == Mode::Flat => {}
+                Doc::Line | Doc::SoftLine | Doc::HardLine => self.newline(ind),
+                Doc::FreshLine => {
+                    let line_start = self.out.rfind('\n').map_or(0, |i| i + 1);
+                    if !self.out[line_start..].trim().is_empty() {
+                        self.newline(ind);
+                    }
+                }
+                Doc::LineSuffix(s) => self.suffixes.push(s.clone()),
+                Doc::BreakParent => {}
+                // A flat group never reaches a new line through its own
+                // breaks, so only indent where the group is broken. This
+                // keeps `f(a, fn() {` from indenting the function body twice.
+                Doc::Indent(doc) if mode == Mode::Flat => stack.push((ind, mode, doc)),
+                Doc::Indent(doc) => stack.push((ind + self.indent, mode, doc)),
+                Doc::Group(doc) => {
+                    let flat = mode == Mode::Flat
+                        || self.fits(self.width as isize - self.column as isize, doc, &stack);
+                    let mode = if flat { Mode::Flat } else { Mode::Break };
+                    stack.push((ind, mode, doc));
+                }
+                Doc::Broken(doc) => stack.push((ind, Mode::Break, doc)),
+                Doc::Concat(docs) => {
+                    for doc in docs.iter().rev() {
+                        stack.push((ind, mode, doc));
+                    }
+                }
+            }
+        }
+    }
+
+    // Reports whether `doc` printed flat, followed by what is left to print,
+    // reaches the end of the line within `remaining` columns. A
+    // `BreakParent` only counts inside `doc`; one further on belongs to
+    // another group.
+    fn fits(&self, mut remaining: isize, doc: &Doc, rest: &[(usize, Mode, &Doc)]) -> bool {
+        let mut stack: Vec<(Mode, &Doc, bool)> = vec![(Mode::Flat, doc, false)];
+        let mut rest = rest.iter().rev();
+
+        loop {
+            let (mode, doc, in_rest) = match stack.pop() {
+                Some(item) => item,
+                None => match rest.next() {
+                    Some((_, mode, doc)) => (*mode, *doc, true),
+                    None => return true,
+                },
+            };
+            match doc {
+                Doc::Text(s) => remaining -= s.chars().count() as isize,
+                Doc::Line if mode == Mode::Flat => remaining -= 1,
+                Doc::SoftLine if mode == Mode::Flat => {}
+                Doc::Line | Doc::SoftLine | Doc::HardLine | Doc::FreshLine => {
+                    return remaining >= 0
+                }
+                Doc::LineSuffix(_) => {}
+                Doc::BreakParent if in_rest => {}
+                Doc::BreakParent => return false,
+                Doc::Indent(doc) | Doc::Group(doc) => stack.push((mode, doc, in_rest)),
+                Doc::Broken(doc) => stack.push((Mode::Break, doc, in_rest)),
+                Doc::Concat(docs) => {
+                    for doc in docs.iter().rev() {
+                        stack.push((mode, doc, in_rest));
+                    }
+                }
+            }
+            if remaining < 0 {
+                return false;
+            }
+        }
+    }
+
+    fn newline(&mut self, ind: usize) {
+        self.flush_suffixes();
+        let trimmed = self.out.trim_end_matches(' ').len();
+        self.out.truncate(trimmed);
+        self.out.push('\n');
+        self.out.push_str(&" ".repeat(ind));
+        self.column = ind;
+    }
+
+    fn flush_suffixes(&mut self) {
+        for suffix in std::mem::take(&mut self.suffixes) {
+            self.out.push(' ');
+            self.out.push_str(&suffix);
+        }
+    }
+
+    fn finish(mut self) -> String {
+        self.flush_suffixes();
+        let mut out = self.out.trim_matches('\n').trim_end().to_string();
+        if !out.is_empty() {
+            out.push('\n');
+        }
+        out
+    }
+}
+
+fn is_line_comment(trivia: &str) -> bool {
+    trivia.starts_with("//")
+}
+
+fn newlines(trivia: &str) -> usize {
+    trivia.matches('\n').count()
+}
+
+// Whether a blank line separated `tok` from whatever came before it.
+fn blank_line_before(tok: &SyntaxToken) -> bool {
+    match tok.leading.first() {
+        Some(trivia) if trivia.token_kind == TokenKind::Whitespace => newlines(&trivia.literal) > 1,
+        _ => false,
+    }
+}
+
+// The comments before a token, each followed by what separated it from the
+// next thing in the source: a line break (kept, along with one blank line)
+// or a space. Comments on the line of the previous token trail that token,
+// so these always started a line of their own and still do.
+fn leading_comments(trivia: &[Token]) -> Vec<Doc> {
+    let mut docs = vec![];
+    if trivia.iter().any(|t| t.token_kind == TokenKind::Comment) {
+        docs.push(Doc::BreakParent);
+        docs.push(Doc::FreshLine);
+    }
+
+    for (i, comment) in trivia.iter().enumerate() {
+        if comment.token_kind != TokenKind::Comment {
+            continue;
+        }
+        let after = trivia
+            .get(i + 1)
+            .filter(|t| t.token_kind == TokenKind::Whitespace)
+            .map_or(0, |t| newlines(&t.literal));
+
+        docs.push(text(comment.literal.trim_end()));
+        if is_line_comment(&comment.literal) || after > 0 {
+            docs.push(Doc::BreakParent);
+            docs.push(Doc::HardLine);
+            if after > 1 {
+                docs.push(Doc::HardLine);
+            }
+        } else {
+            docs.push(text(" "));
+        }
+    }
+
+    docs
+}
+
+fn trailing_comments(tok: &SyntaxToken) -> Vec<Doc> {
+    tok.trailing
+        .iter()
+        .filter(|t| t.token_kind == TokenKind::Comment)
+        .map(|comment| {
+            if is_line_comment(&comment.literal) {
+                Doc::LineSuffix(comment.literal.trim_end().to_string())
+            } else {
+                text(&format!(" {}", comment.literal))
+            }
+        })
+        .collect()
+}
+
+// Prints `tok` as `replacement` (which may be empty), keeping its comments.
+fn token_as(tok: &SyntaxToken, replacement: &str) -> Doc {
+    let mut docs = leading_comments(&tok.leading);
+    docs.push(text(replacement));
+    docs.extend(trailing_comments(tok));
+    Doc::Concat(docs)
+}
+
+fn token(tok: &SyntaxToken) -> Doc {
+    token_as(tok, &tok.text)
+}
+
+// Like `token`, for closing tokens such as `}`: their leading comments
+// belong to the body before them, indented, each on its own line.
+fn closing_token(tok: &SyntaxToken) -> (Doc, Doc) {
+    let mut body = vec![];
+    let mut blank = false;
+    for trivia in tok.leading.iter() {
+        match trivia.token_kind {
+            TokenKind::Whitespace => blank = newlines(&trivia.literal) > 1,
+            _ => {
+                body.push(Doc::BreakParent);
+                body.push(Doc::HardLine);
+                if blank {
+                    body.push(Doc::HardLine);
+                }
+                body.push(text(trivia.literal.trim_end()));
+            }
+        }
+    }
+
+    let mut close = vec![text(&tok.text)];
+    close.extend(trailing_comments(tok));
+    (Doc::Concat(body), Doc::Concat(close))
+}
+
+fn first_token(node: &SyntaxNode) -> Option<&SyntaxToken> {
+    node.children.iter().find_map(|child| match child {
+        SyntaxElement::Token(tok) => Some(tok),
+        SyntaxElement::Node(node) => first_token(node),
+    })
+}
+
+fn first_token_mut(node: &mut SyntaxNode) -> Option<&mut SyntaxToken> {
+    node.children.iter_mut().find_map(|child| match child {
+        SyntaxElement::Token(tok) => Some(tok),
+        SyntaxElement::Node(node) => first_token_mut(node),
+    })
+}
+
+fn program(node: &SyntaxNode) -> Doc {
+    let statements: Vec<&SyntaxNode> = node.child_nodes().collect();
+    let mut docs = vec![];
+
+    for (i, stmt) in statements.iter().enumerate() {
+        if i > 0 {
+            docs.push(Doc::HardLine);
+            if first_token(stmt).is_some_and(blank_line_before) {
+                docs.push(Doc::HardLine);
+            }
+        }
+        docs.push(statement(stmt, false));
+    }
+
+    if let Some(eof) = node.child_tokens().last() {
+        let (comments, _) = closing_token(eof);
+        docs.push(comments);
+    }
+
+    Doc::Concat(docs)
+}
+
+fn block(node: &SyntaxNode) -> Doc {
+    let open = node.child_tokens().next().unwrap();
+    let close = node.child_tokens().nth(1).unwrap();
+    let statements: Vec<&SyntaxNode> = node.child_nodes().collect();
+    let (comments, close) = closing_token(close);
+
+    if statements.is_empty() && close_has_no_comments(node) {
+        return Doc::Concat(vec![token(open), close]);
+    }
+
+    let mut body = vec![];
+    for (i, stmt) in statements.iter().enumerate() {
+        body.push(Doc::HardLine);
+        if i > 0 && first_token(stmt).is_some_and(blank_line_before) {
+            body.push(Doc::HardLine);
+        }
+        body.push(statement(stmt, i + 1 == statements.len()));
+    }
+    body.push(comments);
+
+    Doc::Concat(vec![
+        token(open),
+        Doc::Broken(Box::new(Doc::Concat(vec![
+            indent(Doc::Concat(body)),
+            Doc::HardLine,
+        ]))),
+        close,
+    ])
+}
+
+fn close_has_no_comments(node: &SyntaxNode) -> bool {
+    node.child_tokens().nth(1).is_some_and(|tok| {
+        tok.leading
+            .iter()
+            .all(|t| t.token_kind != TokenKind::Comment)
+    })
+}
+
+fn statement(node: &SyntaxNode, last_in_block: bool) -> Doc {
+    // Comments above the statement go before it rather than inside the
+    // groups of whatever expression it starts with, which they would
+    // otherwise break.
+    let mut node = node.clone();
+    let mut docs = match first_token_mut(&mut node) {
+        Some(tok) => leading_comments(&std::mem::take(&mut tok.leading)),
+        None => vec![],
+    };
+    let node = &node;
+    let mut semicolon = None;
+
+    for child in node.children.iter() {
+        match child {
+            SyntaxElement::Token(tok) if tok.kind() == &TokenKind::Semicolon => {
+                semicolon = Some(tok)
+            }
+            SyntaxElement::Token(tok) => {
+                // `let` and `return` are followed by a space, `=` by one on
+                // either side.
+                if tok.kind() == &TokenKind::Assign {
+                    docs.push(text(" "));
+                }
+                docs.push(token(tok));
+                docs.push(text(" "));
+            }
+            SyntaxElement::Node(child) => docs.push(expression(child)),
+        }
+    }
+
+    // Semicolons are added where missing but never removed: without one, a
+    // following `(` or `[` could continue the expression as a call or index.
+    let needs_semicolon = match node.kind {
+        SyntaxKind::ExpressionStatement => {
+            let is_if = node
+                .child_nodes()
+                .next()
+                .is_some_and(|e| e.kind == SyntaxKind::IfExpression);
+            !is_if && !last_in_block
+        }
+        _ => true,
+    };
+    match semicolon {
+        Some(tok) => docs.push(token(tok)),
+        None if needs_semicolon => docs.push(text(";")),
+        None => {}
+    }
+
+    Doc::Concat(docs)
+}
+
+fn expression(node: &SyntaxNode) -> Doc {
+    match node.kind {
+        SyntaxKind::InfixExpression => {
+            let mut children = node.children.iter();
+            let left = element(children.next().unwrap());
+            let operator = element(children.next().unwrap());
+            let right = element(children.next().unwrap());
+            group(Doc::Concat(vec![
+                left,
+                text(" "),
+                operator,
+                indent(Doc::Concat(vec![Doc::Line, right])),
+            ]))
+        }
+        SyntaxKind::IfExpression => {
+            let mut docs = vec![];
+            for child in node.children.iter() {
+                match child {
+                    SyntaxElement::Token(tok) => match tok.kind() {
+                        TokenKind::If | TokenKind::RParen => {
+                            docs.push(token(tok));
+                            docs.push(text(" "));
+                        }
+                        TokenKind::Else => {
+                            docs.push(text(" "));
+                            docs.push(token(tok));
+                            docs.push(text(" "));
+                        }
+                        _ => docs.push(token(tok)),
+                    },
+                    SyntaxElement::Node(child) => docs.push(expression(child)),
+                }
+            }
+            Doc::Concat(docs)
+        }
+        SyntaxKind::FunctionLiteral => {
+            let mut docs = vec![];
+            for child in node.children.iter() {
+                match child {
+                    SyntaxElement::Node(child) if child.kind == SyntaxKind::BlockStatement => {
+                        docs.push(text(" "));
+                        docs.push(block(child));
+                    }
+                    child => docs.push(element(child)),
+                }
+            }
+            Doc::Concat(docs)
+        }
+        SyntaxKind::BlockStatement => block(node),
+        SyntaxKind::ParameterList
+        | SyntaxKind::ArgumentList
+        | SyntaxKind::ArrayLiteral
+        | SyntaxKind::HashLiteral => list(node),
+        SyntaxKind::HashPair => {
+            let mut docs = vec![];
+            for child in node.children.iter() {
+                docs.push(element(child));
+                if let SyntaxElement::Token(_) = child {
+                    docs.push(text(" "));
+                }
+            }
+            Doc::Concat(docs)
+        }
+        // Everything else is printed tight: prefix operators, groups,
+        // calls, index expressions and single tokens.
+        _ => Doc::Concat(node.children.iter().map(element).collect()),
+    }
+}
+
+fn element(element: &SyntaxElement) -> Doc {
+    match element {
+        SyntaxElement::Node(node) => expression(node),
+        SyntaxElement::Token(tok) => token(tok),
+    }
+}
+
+// A bracketed, comma separated list: `[a, b]` when it fits, otherwise one
+// item per line. A trailing comma is dropped, as arrays and calls do not
+// accept one.
+fn list(node: &SyntaxNode) -> Doc {
+    let children = &node.children;
+    let (open, close) = match (children.first(), children.last()) {
+        (Some(SyntaxElement::Token(open)), Some(SyntaxElement::Token(close))) => (open, close),
+        _ => unreachable!("lists are bracketed"),
+    };
+    let inner = &children[1..children.len() - 1];
+    if inner.is_empty() && close_has_no_comments(node) {
+        return Doc::Concat(vec![token(open), token(close)]);
+    }
+
+    // Closing comments start their own lines.
+    let mut items = if inner.is_empty() {
+        vec![]
+    } else {
+        vec![Doc::SoftLine]
+    };
+    for (i, child) in inner.iter().enumerate() {
+        match child {
+            SyntaxElement::Token(comma) if i + 1 == inner.len() => items.push(token_as(comma, "")),
+            SyntaxElement::Token(comma) => {
+                items.push(token(comma));
+                items.push(Doc::Line);
+            }
+            SyntaxElement::Node(item) => items.push(expression(item)),
+        }
+    }
+
+    let (comments, close) = closing_token(close);
+    items.push(comments);
+    group(Doc::Concat(vec![
+        token(open),
+        indent(Doc::Concat(items)),
+        Doc::SoftLine,
+        close,
+    ]))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::formatter::Formatter;
+    use crate::{ast::Node, lexer::Lexer, parser::Parser};
+
+    fn format(input: &str) -> String {
+        Formatter::new().format(input).unwrap()
+    }
+
+    fn ast_string(input: &str) -> String {
+        let mut p = Parser::new(Lexer::new(input.to_string()));
+        p.parse_program().string()
+    }
+
+    #[test]
+    fn test_format() {
+        let tests = vec![
+            ("", ""),
+            ("let x=5", "let x = 5;\n"),
+            ("return   x*  -y", "return x * -y;\n"),
+            ("a+b;c", "a + b;\nc;\n"),
+            (
+                "let f=fn(a,b){let c=a+b;c}",
+                "let f = fn(a, b) {\n    let c = a + b;\n    c\n};\n",
+            ),
+            ("fn(){}", "fn() {};\n"),
+            (
+                "if(x<y){x}else{ y; }",
+                "if (x < y) {\n    x\n} else {\n    y;\n}\n",
+            ),
+            (
+                "let h={\"a\":1,true:[1,2],}",
+                "let h = {\"a\": 1, true: [1, 2]};\n",
+            ),
+            ("(1+2)*add(x)[0]", "(1 + 2) * add(x)[0];\n"),
+            (
+                "let s = \"a\\n\\u{1F600}\";",
+                "let s = \"a\\n\\u{1F600}\";\n",
+            ),
+            ("map(arr,fn(x){x*2})", "map(arr, fn(x) {\n    x * 2\n});\n"),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(format(input), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_format_comments_and_blank_lines() {
+        let input = "// Doubles things.\nlet double = fn(x) { // inline\n  /* the work */ x * 2\n\n\n  // done\n};\n\n\n\nlet y = double(2); // four\n/* end */\n";
+        let expected = "// Doubles things.\nlet double = fn(x) { // inline\n    /* the work */ x * 2\n\n    // done\n};\n\nlet y = double(2); // four\n/* end */\n";
+
+        assert_eq!(format(input), expected);
+    }
+
+    #[test]
+    fn test_format_width() {
+        let input = "let result = reduce([1, 2, 3, 4, 5], 0, fn(acc, x) { acc + x });";
+
+        assert_eq!(
+            format(input),
+            format!("{}\n", input).replace("{ acc + x }", "{\n    acc + x\n}")
+        );
+
+        let narrow = Formatter::new().with_width(20).with_indent(2);
+        assert_eq!(
+            narrow.format("let list = [first, second, third];").unwrap(),
+            "let list = [\n  first,\n  second,\n  third\n];\n"
+        );
+        assert_eq!(
+            narrow.format("let total = alpha + beta + gamma;").unwrap(),
+            "let total = alpha +\n  beta +\n  gamma;\n"
+        );
+        assert_eq!(
+            narrow.format("f(a, // the first\n b)").unwrap(),
+            "f(a, b); // the first\n"
+        );
+        assert_eq!(
+            narrow.format("f(a,\n // before b\n b)").unwrap(),
+            "f(\n  a,\n  // before b\n  b\n);\n"
+        );
+    }
+
+    #[test]
+    fn test_format_is_stable() {
+        let inputs = vec![
+            "let fib=fn(n){if(n<2){return n;}fib(n-1)+fib(n-2)};fib(10)",
+            "let people=[{\"name\":\"Anna\",\"age\":24},{\"name\":\"Bob\",\"age\":99}]; // people\n\n\nputs(people[0][\"name\"])",
+            "/* a */ let x = 1; /* b */ // c\n// d\nx",
+            "let grüße = fn(名前) { \"¡hola, \" + 名前 }; grüße(\"Zoë\")",
+        ];
+
+        for input in inputs {
+            let once = format(input);
+            assert_eq!(format(&once), once, "{}", input);
+            assert_eq!(ast_string(&once), ast_string(input), "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_format_syntax_error() {
+        let errors = Formatter::new().format("let = 1;").unwrap_err();
+        assert_eq!(errors[0].to_string(), "1:5: expected Ident, got Assign");
+    }
+}
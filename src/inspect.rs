@@ -0,0 +1,237 @@
+//! Renderings of a parsed program for the REPL's `:ast` and `:sexpr`
+//! commands.
+
+use monkey_rs::{
+    ast::{BlockStatement, Expression, Identifier, Node, Statement},
+    Program,
+};
+
+/// A node of the program reduced to what both renderings need: the AST
+/// type, what the s-expression leads with, and the node's children.
+struct Tree {
+    kind: &'static str,
+    head: String,
+    // Operators and literal values, shown next to the kind by `:ast`.
+    detail: Option<String>,
+    children: Vec<Tree>,
+}
+
+impl Tree {
+    fn node(kind: &'static str, head: &str, children: Vec<Tree>) -> Tree {
+        Tree {
+            kind,
+            head: head.to_string(),
+            detail: None,
+            children,
+        }
+    }
+
+    fn leaf(kind: &'static str, value: String) -> Tree {
+        Tree {
+            kind,
+            head: value.clone(),
+            detail: Some(value),
+            children: vec![],
+        }
+    }
+
+    fn operator(kind: &'static str, operator: &str, children: Vec<Tree>) -> Tree {
+        Tree {
+            detail: Some(operator.to_string()),
+            ..Tree::node(kind, operator, children)
+        }
+    }
+
+    fn sexpr(&self) -> String {
+        if self.children.is_empty() && self.detail.is_some() {
+            return self.head.clone();
+        }
+        let mut parts = vec![self.head.clone()];
+        parts.extend(self.children.iter().map(|child| child.sexpr()));
+        format!("({})", parts.join(" "))
+    }
+
+    fn write_indented(&self, depth: usize, out: &mut String) {
+        out.push_str(&"  ".repeat(depth));
+        out.push_str(self.kind);
+        if let Some(detail) = &self.detail {
+            out.push(' ');
+            out.push_str(detail);
+        }
+        out.push('\n');
+        for child in self.children.iter() {
+            child.write_indented(depth + 1, out);
+        }
+    }
+}
+
+/// Renders `program` as one s-expression per statement, such as
+/// `(let x (+ 1 (* 2 3)))`.
+pub fn sexpr(program: &Program) -> String {
+    let statements: Vec<String> = program
+        .statements
+        .iter()
+        .map(|stmt| statement(stmt).sexpr())
+        .collect();
+    statements.join("\n")
+}
+
+/// Renders `program` as an indented tree of AST node types.
+pub fn tree(program: &Program) -> String {
+    let root = Tree::node(
+        "Program",
+        "program",
+        program.statements.iter().map(statement).collect(),
+    );
+    let mut out = String::new();
+    root.write_indented(0, &mut out);
+    out.trim_end().to_string()
+}
+
+fn statement(stmt: &Statement) -> Tree {
+    match stmt {
+        Statement::Let(s) => Tree::node(
+            "LetStatement",
+            "let",
+            vec![identifier(&s.name), expression(&s.value)],
+        ),
+        Statement::Return(s) => Tree::node(
+            "ReturnStatement",
+            "return",
+            vec![expression(&s.return_value)],
+        ),
+        Statement::Expression(s) => expression(&s.expression),
+        Statement::Error(_) => Tree::node("ErrorStatement", "error", vec![]),
+    }
+}
+
+fn block(block: &BlockStatement) -> Tree {
+    Tree::node(
+        "BlockStatement",
+        "block",
+        block.statements.iter().map(statement).collect(),
+    )
+}
+
+fn identifier(ident: &Identifier) -> Tree {
+    Tree::leaf("Identifier", ident.value.clone())
+}
+
+fn expression(exp: &Expression) -> Tree {
+    match exp {
+        Expression::Identifier(e) => identifier(e),
+        Expression::IntegerLiteral(e) => Tree::leaf("IntegerLiteral", e.value.to_string()),
+        Expression::StringLiteral(e) => Tree::leaf("StringLiteral", e.string()),
+        Expression::Boolean(e) => Tree::leaf("Boolean", e.value.to_string()),
+        Expression::Prefix(e) => {
+            Tree::operator("PrefixExpression", &e.operator, vec![expression(&e.right)])
+        }
+        Expression::Infix(e) => Tree::operator(
+            "InfixExpression",
+            &e.operator,
+            vec![expression(&e.left), expression(&e.right)],
+        ),
+        Expression::If(e) => {
+            let mut children = vec![expression(&e.condition), block(&e.consequence)];
+            children.extend(e.alternative.as_ref().map(block));
+            Tree::node("IfExpression", "if", children)
+        }
+        Expression::Function(e) => Tree::node(
+            "FunctionLiteral",
+            "fn",
+            vec![
+                Tree::node(
+                    "Parameters",
+                    "params",
+                    e.parameters.iter().map(identifier).collect(),
+                ),
+                block(&e.body),
+            ],
+        ),
+        Expression::Call(e) => {
+            let mut children = vec![expression(&e.function)];
+            children.extend(e.arguments.iter().map(expression));
+            Tree::node("CallExpression", "call", children)
+        }
+        Expression::Array(e) => Tree::node(
+            "ArrayLiteral",
+            "array",
+            e.elements.iter().map(expression).collect(),
+        ),
+        Expression::Index(e) => Tree::node(
+            "IndexExpression",
+            "index",
+            vec![expression(&e.left), expression(&e.index)],
+        ),
+        Expression::Hash(e) => Tree::node(
+            "HashLiteral",
+            "hash",
+            e.pairs
+                .iter()
+                .map(|(key, value)| {
+                    Tree::node("Pair", "pair", vec![expression(key), expression(value)])
+                })
+                .collect(),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::inspect::{sexpr, tree};
+    use monkey_rs::{Lexer, Parser, Program};
+
+    fn parse(input: &str) -> Program {
+        let mut p = Parser::new(Lexer::new(input.to_string()));
+        let program = p.parse_program();
+        assert!(p.errors.is_empty(), "parser errors: {:?}", p.errors);
+        program
+    }
+
+    #[test]
+    fn test_sexpr() {
+        let tests = vec![
+            ("let x = 1 + 2 * 3;", "(let x (+ 1 (* 2 3)))"),
+            ("return -a;", "(return (- a))"),
+            (
+                "if (x < y) { x } else { y; z }",
+                "(if (< x y) (block x) (block y z))",
+            ),
+            (
+                "fn(a, b) { a }(1, \"s\")",
+                "(call (fn (params a b) (block a)) 1 \"s\")",
+            ),
+            ("fn() {}", "(fn (params) (block))"),
+            ("[1, true][0]", "(index (array 1 true) 0)"),
+            (
+                "{\"k\": [], 2: !x}",
+                "(hash (pair \"k\" (array)) (pair 2 (! x)))",
+            ),
+            ("a; b", "a\nb"),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(sexpr(&parse(input)), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_tree() {
+        assert_eq!(
+            tree(&parse("let f = fn(x) { x * 2 }; f(1)")),
+            "Program
+  LetStatement
+    Identifier f
+    FunctionLiteral
+      Parameters
+        Identifier x
+      BlockStatement
+        InfixExpression *
+          Identifier x
+          IntegerLiteral 2
+  CallExpression
+    Identifier f
+    IntegerLiteral 1"
+        );
+    }
+}
@@ -0,0 +1,171 @@
+use std::fmt;
+
+use crate::{
+    builtins::Builtins,
+    diagnostic::Diagnostic,
+    environment::Environment,
+    evaluator,
+    lexer::Lexer,
+    object::{Arity, Object},
+    parser::Parser,
+};
+
+/// The result of evaluating Monkey code.
+pub type Value = Object;
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Error {
+    /// The source could not be parsed; holds every parser error.
+    Parse(Vec<Diagnostic>),
+    /// Evaluation produced an error object; holds its message.
+    Runtime(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Parse(errors) => {
+                let errors: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+                write!(f, "parse error: {}", errors.join("; "))
+            }
+            Error::Runtime(message) => write!(f, "runtime error: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Evaluates Monkey source against a single global environment, so bindings
+/// made by one call to [`Interpreter::eval_str`] are visible to the next.
+#[derive(Default)]
+pub struct Interpreter {
+    env: Environment,
+}
+
+impl Interpreter {
+    pub fn new() -> Interpreter {
+        Interpreter {
+            env: Environment::new(),
+        }
+    }
+
+    pub fn with_builtins(builtins: Builtins) -> Interpreter {
+        Interpreter {
+            env: Environment::with_builtins(builtins),
+        }
+    }
+
+    /// Makes a native function callable from Monkey code; see
+    /// [`Builtins::register`].
+    pub fn register_builtin<F>(&mut self, name: &str, arity: Arity, func: F)
+    where
+        F: Fn(&[Object]) -> Object + 'static,
+    {
+        self.env.register_builtin(name, arity, func);
+    }
+
+    pub fn environment(&self) -> &Environment {
+        &self.env
+    }
+
+    pub fn eval_str(&mut self, input: &str) -> Result<Value, Error> {
+        let lexer = Lexer::new(input.to_string());
+        let mut p = Parser::new(lexer);
+        let program = p.parse_program();
+        if !p.errors.is_empty() {
+            return Err(Error::Parse(p.errors));
+        }
+
+        match evaluator::eval(&program, &mut self.env) {
+            Object::Error(message) => Err(Error::Runtime(message)),
+            value => Ok(value),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::interpreter::{Error, Interpreter, Value};
+    use crate::object::{Arity, Object};
+
+    #[test]
+    fn test_eval_str() {
+        let mut interpreter = Interpreter::new();
+
+        assert_eq!(interpreter.eval_str("1 + 2 * 3"), Ok(Value::Integer(7)));
+        assert_eq!(interpreter.eval_str("let x = 5;"), Ok(Value::Null));
+        assert_eq!(interpreter.eval_str("x * 2"), Ok(Value::Integer(10)));
+    }
+
+    #[test]
+    fn test_environment_bindings() {
+        let mut interpreter = Interpreter::new();
+        interpreter
+            .eval_str("let b = 2; let a = 1; let f = fn(x) { let inner = x; inner };")
+            .unwrap();
+        interpreter.eval_str("f(3)").unwrap();
+
+        let names: Vec<String> = interpreter
+            .environment()
+            .bindings()
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect();
+        assert_eq!(names, vec!["a", "b", "f"]);
+        assert_eq!(interpreter.environment().bindings()[0].1, Value::Integer(1));
+    }
+
+    #[test]
+    fn test_eval_str_unicode() {
+        let mut interpreter = Interpreter::new();
+
+        assert_eq!(
+            interpreter
+                .eval_str(r#"let grüße = fn(名前) { "¡hola, " + 名前 + "!" }; grüße("Zoë")"#),
+            Ok(Value::String(String::from("¡hola, Zoë!")))
+        );
+    }
+
+    #[test]
+    fn test_eval_str_errors() {
+        let mut interpreter = Interpreter::new();
+
+        match interpreter.eval_str("let = 5;") {
+            Err(err @ Error::Parse(_)) => {
+                assert_eq!(
+                    err.to_string(),
+                    "parse error: 1:5: expected Ident, got Assign"
+                )
+            }
+            result => panic!("expected parse error, got {:?}", result),
+        }
+        assert_eq!(
+            interpreter.eval_str("5 + true"),
+            Err(Error::Runtime(String::from(
+                "type mismatch: INTEGER + BOOLEAN"
+            )))
+        );
+    }
+
+    #[test]
+    fn test_register_builtin() {
+        let mut interpreter = Interpreter::new();
+        interpreter.register_builtin("shout", Arity::Exact(1), |args| match &args[0] {
+            Object::String(s) => Object::String(s.to_uppercase()),
+            arg => Object::Error(format!("cannot shout {}", arg.type_name())),
+        });
+
+        assert_eq!(
+            interpreter.eval_str(r#"shout("hi")"#),
+            Ok(Value::String(String::from("HI")))
+        );
+        assert_eq!(
+            interpreter.eval_str("shout(1)"),
+            Err(Error::Runtime(String::from("cannot shout INTEGER")))
+        );
+        assert!(interpreter
+            .environment()
+            .builtin_names()
+            .contains(&String::from("shout")));
+    }
+}
@@ -1,10 +1,14 @@
-use crate::token::{lookup_ident, Token, TokenKind};
+use crate::token::{lookup_ident, Span, Token, TokenKind};
 #[derive(PartialEq)]
 pub struct Lexer {
     input: String,
-    position: usize,      // current position in input (points to current char)
-    read_position: usize, // current reading position in input (after current ch
-    ch: u8,               // current char under examination
+    position: usize,       // byte offset of the current char in input
+    read_position: usize,  // byte offset of the char after it
+    ch: char,              // current char under examination, '\0' past the end
+    line: usize,           // line of the current char, starting at 1
+    column: usize,         // column of the current char in chars, starting at 1
+    keep_comments: bool,   // return comments as tokens instead of skipping them
+    keep_whitespace: bool, // likewise for runs of whitespace
 }
 
 impl Lexer {
@@ -13,137 +17,358 @@ impl Lexer {
             input,
             position: 0,
             read_position: 0,
-            ch: 0,
+            ch: '\0',
+            line: 1,
+            column: 0,
+            keep_comments: false,
+            keep_whitespace: false,
         };
         l.read_char();
         l
     }
 
-    fn new_token(token_kind: TokenKind, ch: u8) -> Token {
+    /// Makes the lexer return comments as [`TokenKind::Comment`] tokens
+    /// rather than skipping them, for tools that need to put them back.
+    pub fn with_comments(mut self, keep_comments: bool) -> Self {
+        self.keep_comments = keep_comments;
+        self
+    }
+
+    /// Makes the lexer return runs of whitespace as [`TokenKind::Whitespace`]
+    /// tokens. Together with comments this accounts for every byte of the
+    /// input, which is what the [`cst`](crate::cst) needs.
+    pub fn with_whitespace(mut self, keep_whitespace: bool) -> Self {
+        self.keep_whitespace = keep_whitespace;
+        self
+    }
+
+    fn new_token(token_kind: TokenKind, ch: char) -> Token {
         Token {
             token_kind,
-            literal: String::from_utf8(vec![ch]).unwrap(),
+            literal: ch.to_string(),
+            span: Span::default(),
         }
     }
 
     fn read_char(&mut self) {
-        if self.read_position >= self.input.len() {
-            self.ch = 0;
-        } else {
-            self.ch = self.input.as_bytes()[self.read_position];
+        // Already past the end; stay on the '\0' there.
+        if self.read_position > self.input.len() {
+            return;
+        }
+        if self.ch == '\n' {
+            self.line += 1;
+            self.column = 0;
         }
         self.position = self.read_position;
-        self.read_position += 1
+        match self.input[self.position..].chars().next() {
+            Some(ch) => {
+                self.ch = ch;
+                self.read_position += ch.len_utf8();
+            }
+            None => {
+                self.ch = '\0';
+                self.read_position += 1;
+            }
+        }
+        self.column += 1;
     }
 
-    fn peek_char(&mut self) -> u8 {
-        if self.read_position >= self.input.len() {
-            return 0;
-        }
-        self.input.as_bytes()[self.read_position]
+    fn peek_char(&self) -> char {
+        self.input
+            .get(self.read_position..)
+            .and_then(|rest| rest.chars().next())
+            .unwrap_or('\0')
+    }
+
+    fn at_end(&self) -> bool {
+        self.position >= self.input.len()
     }
 
     fn read_identifier(&mut self) -> String {
         let position = self.position;
-        while Self::is_letter(&self.ch) {
+        while Self::is_identifier_continue(self.ch) {
             self.read_char();
         }
-        self.input.get(position..self.position).unwrap().to_string()
+        self.input[position..self.position].to_string()
     }
 
-    fn is_letter(ch: &u8) -> bool {
-        let ch = char::from(*ch);
-        'a' <= ch && ch <= 'z' || 'A' <= ch && ch <= 'Z' || ch == '_'
+    /// Identifiers follow Unicode's UAX #31: they start with an XID_Start
+    /// character or `_` and continue with XID_Continue characters.
+    fn is_identifier_start(ch: char) -> bool {
+        ch == '_' || unicode_ident::is_xid_start(ch)
+    }
+
+    fn is_identifier_continue(ch: char) -> bool {
+        unicode_ident::is_xid_continue(ch)
     }
 
     fn read_number(&mut self) -> String {
         let position = self.position;
-        while Self::is_digit(&self.ch) {
+        while Self::is_digit(self.ch) {
             self.read_char();
         }
-        self.input.get(position..self.position).unwrap().to_string()
+        self.input[position..self.position].to_string()
     }
 
-    fn is_digit(ch: &u8) -> bool {
-        let ch = char::from(*ch);
-        '0' <= ch && ch <= '9'
+    fn is_digit(ch: char) -> bool {
+        ch.is_ascii_digit()
     }
 
-    fn skip_whitespace(&mut self) {
-        while self.ch == b' ' || self.ch == b'\t' || self.ch == b'\n' || self.ch == b'\r' {
+    /// Reads a double-quoted string starting at the opening quote and returns
+    /// its decoded value, or a message describing why it is malformed. The
+    /// lexer is left on the closing quote.
+    fn read_string(&mut self) -> Result<String, String> {
+        let mut out = String::new();
+        let mut error = None;
+
+        loop {
             self.read_char();
+            if self.at_end() {
+                return Err(String::from("unterminated string literal"));
+            }
+
+            match self.ch {
+                '"' => break,
+                '\\' => {
+                    self.read_char();
+                    if self.at_end() {
+                        return Err(String::from("unterminated string literal"));
+                    }
+                    match self.read_escape() {
+                        Ok(ch) => out.push(ch),
+                        Err(msg) => {
+                            error.get_or_insert(msg);
+                        }
+                    }
+                }
+                ch => out.push(ch),
+            }
+        }
+
+        match error {
+            Some(msg) => Err(msg),
+            None => Ok(out),
+        }
+    }
+
+    /// Decodes the escape sequence whose first character (after the
+    /// backslash) is under examination.
+    fn read_escape(&mut self) -> Result<char, String> {
+        match self.ch {
+            'n' => Ok('\n'),
+            't' => Ok('\t'),
+            '"' => Ok('"'),
+            '\\' => Ok('\\'),
+            'u' => {
+                if self.peek_char() != '{' {
+                    return Err(String::from(
+                        "invalid unicode escape: expected '{' after \\u",
+                    ));
+                }
+                self.read_char();
+
+                let mut digits = String::new();
+                while self.peek_char().is_ascii_hexdigit() && digits.len() < 6 {
+                    self.read_char();
+                    digits.push(self.ch);
+                }
+                if self.peek_char() != '}' || digits.is_empty() {
+                    return Err(String::from(
+                        "invalid unicode escape: expected 1 to 6 hex digits and '}'",
+                    ));
+                }
+                self.read_char();
+
+                u32::from_str_radix(&digits, 16)
+                    .ok()
+                    .and_then(char::from_u32)
+                    .ok_or_else(|| {
+                        format!("invalid unicode escape: {:?} is not a character", digits)
+                    })
+            }
+            ch => Err(format!("unknown escape sequence: \\{}", ch)),
+        }
+    }
+
+    fn is_whitespace(ch: char) -> bool {
+        matches!(ch, ' ' | '\t' | '\n' | '\r')
+    }
+
+    fn read_whitespace(&mut self) -> Token {
+        let position = self.position;
+        while Self::is_whitespace(self.ch) {
+            self.read_char();
+        }
+        Token {
+            token_kind: TokenKind::Whitespace,
+            literal: self.input[position..self.position].to_string(),
+            span: Span::default(),
+        }
+    }
+
+    /// Reads a `//` comment up to the end of the line, or a `/* */` comment
+    /// with any comments nested inside it. The lexer is left on the char
+    /// after the comment.
+    fn read_comment(&mut self) -> Token {
+        let position = self.position;
+
+        if self.peek_char() == '/' {
+            while self.ch != '\n' && !self.at_end() {
+                self.read_char();
+            }
+        } else {
+            let mut depth = 0;
+            loop {
+                if self.at_end() {
+                    return Token {
+                        token_kind: TokenKind::Illegal,
+                        literal: String::from("unterminated block comment"),
+                        span: Span::default(),
+                    };
+                }
+                match (self.ch, self.peek_char()) {
+                    ('/', '*') => {
+                        depth += 1;
+                        self.read_char();
+                    }
+                    ('*', '/') => {
+                        depth -= 1;
+                        self.read_char();
+                    }
+                    _ => {}
+                }
+                self.read_char();
+                if depth == 0 {
+                    break;
+                }
+            }
+        }
+
+        Token {
+            token_kind: TokenKind::Comment,
+            literal: self.input[position..self.position].to_string(),
+            span: Span::default(),
         }
     }
 
     pub fn next_token(&mut self) -> Token {
-        self.skip_whitespace();
+        loop {
+            let start = self.position.min(self.input.len());
+            let line = self.line;
+            let column = self.column;
+
+            let mut tok = if Self::is_whitespace(self.ch) {
+                self.read_whitespace()
+            } else if self.ch == '/' && matches!(self.peek_char(), '/' | '*') {
+                self.read_comment()
+            } else {
+                self.read_token()
+            };
+            let skip = match tok.token_kind {
+                TokenKind::Whitespace => !self.keep_whitespace,
+                TokenKind::Comment => !self.keep_comments,
+                _ => false,
+            };
+            if skip {
+                continue;
+            }
+
+            tok.span = Span {
+                start,
+                end: self.position.min(self.input.len()),
+                line,
+                column,
+            };
+            return tok;
+        }
+    }
 
+    fn read_token(&mut self) -> Token {
         let tok;
         match self.ch {
-            b'=' => {
-                if self.peek_char() == b'=' {
-                    let current_ch = String::from_utf8(vec![self.ch]).unwrap();
+            '=' => {
+                if self.peek_char() == '=' {
                     self.read_char();
-                    let next_ch = String::from_utf8(vec![self.ch]).unwrap();
                     tok = Token {
                         token_kind: TokenKind::Eq,
-                        literal: format!("{}{}", current_ch, next_ch),
+                        literal: String::from("=="),
+                        span: Span::default(),
                     };
                 } else {
                     tok = Self::new_token(TokenKind::Assign, self.ch);
                 }
             }
-            b'!' => {
-                if self.peek_char() == b'=' {
-                    let current_ch = String::from_utf8(vec![self.ch]).unwrap();
+            '!' => {
+                if self.peek_char() == '=' {
                     self.read_char();
-                    let next_ch = String::from_utf8(vec![self.ch]).unwrap();
                     tok = Token {
                         token_kind: TokenKind::NotEq,
-                        literal: format!("{}{}", current_ch, next_ch),
+                        literal: String::from("!="),
+                        span: Span::default(),
                     };
                 } else {
                     tok = Self::new_token(TokenKind::Bang, self.ch);
                 }
             }
-            b'+' => tok = Self::new_token(TokenKind::Plus, self.ch),
-            b'-' => tok = Self::new_token(TokenKind::Minus, self.ch),
-            b'/' => tok = Self::new_token(TokenKind::Slash, self.ch),
-            b'*' => tok = Self::new_token(TokenKind::Asterisk, self.ch),
-            b'<' => tok = Self::new_token(TokenKind::Lt, self.ch),
-            b'>' => tok = Self::new_token(TokenKind::Gt, self.ch),
-            b';' => tok = Self::new_token(TokenKind::Semicolon, self.ch),
-            b'(' => tok = Self::new_token(TokenKind::LParen, self.ch),
-            b')' => tok = Self::new_token(TokenKind::RParen, self.ch),
-            b',' => tok = Self::new_token(TokenKind::Comma, self.ch),
-            b'{' => tok = Self::new_token(TokenKind::LBrace, self.ch),
-            b'}' => tok = Self::new_token(TokenKind::RBrace, self.ch),
-            0 => {
+            '+' => tok = Self::new_token(TokenKind::Plus, self.ch),
+            '-' => tok = Self::new_token(TokenKind::Minus, self.ch),
+            '/' => tok = Self::new_token(TokenKind::Slash, self.ch),
+            '*' => tok = Self::new_token(TokenKind::Asterisk, self.ch),
+            '<' => tok = Self::new_token(TokenKind::Lt, self.ch),
+            '>' => tok = Self::new_token(TokenKind::Gt, self.ch),
+            ';' => tok = Self::new_token(TokenKind::Semicolon, self.ch),
+            ':' => tok = Self::new_token(TokenKind::Colon, self.ch),
+            '(' => tok = Self::new_token(TokenKind::LParen, self.ch),
+            ')' => tok = Self::new_token(TokenKind::RParen, self.ch),
+            ',' => tok = Self::new_token(TokenKind::Comma, self.ch),
+            '{' => tok = Self::new_token(TokenKind::LBrace, self.ch),
+            '}' => tok = Self::new_token(TokenKind::RBrace, self.ch),
+            '[' => tok = Self::new_token(TokenKind::LBracket, self.ch),
+            ']' => tok = Self::new_token(TokenKind::RBracket, self.ch),
+            '"' => {
+                tok = match self.read_string() {
+                    Ok(literal) => Token {
+                        token_kind: TokenKind::String,
+                        literal,
+                        span: Span::default(),
+                    },
+                    Err(msg) => Token {
+                        token_kind: TokenKind::Illegal,
+                        literal: msg,
+                        span: Span::default(),
+                    },
+                };
+            }
+            '\0' if self.at_end() => {
                 tok = Token {
                     token_kind: TokenKind::Eof,
                     literal: String::from(""),
+                    span: Span::default(),
                 };
             }
             _ => {
-                if Self::is_letter(&self.ch) {
+                if Self::is_identifier_start(self.ch) {
                     let ident = self.read_identifier();
                     let tk_kind = lookup_ident(&ident);
                     tok = Token {
                         token_kind: tk_kind,
                         literal: ident,
+                        span: Span::default(),
                     };
                     return tok;
-                } else if Self::is_digit(&self.ch) {
+                } else if Self::is_digit(self.ch) {
                     let ident = self.read_number();
                     tok = Token {
                         token_kind: TokenKind::Int,
                         literal: ident,
+                        span: Span::default(),
                     };
                     return tok;
                 } else {
                     tok = Token {
                         token_kind: TokenKind::Illegal,
-                        literal: String::from(""),
+                        literal: format!("illegal character {:?}", self.ch),
+                        span: Span::default(),
                     };
                 }
             }
@@ -156,7 +381,7 @@ impl Lexer {
 #[cfg(test)]
 mod tests {
     use crate::lexer::Lexer;
-    use crate::token::TokenKind;
+    use crate::token::{Span, TokenKind, KEYWORDS};
 
     #[test]
     fn test_next_token() {
@@ -167,7 +392,7 @@ mod tests {
                  x + y;
             };
             let result = add(five, ten);
-            !-/*5;
+            !-/ *5;
             5 < 10 > 5;
 
             if (5 < 10) {
@@ -178,6 +403,8 @@ mod tests {
 
             10 == 10;
             10 != 9;
+            [1, 2];
+            {\"foo\": \"bar\"}
             ",
         );
         let mut lexer = Lexer::new(input);
@@ -256,14 +483,263 @@ mod tests {
             (TokenKind::NotEq, String::from("!=")),
             (TokenKind::Int, String::from("9")),
             (TokenKind::Semicolon, String::from(";")),
+            (TokenKind::LBracket, String::from("[")),
+            (TokenKind::Int, String::from("1")),
+            (TokenKind::Comma, String::from(",")),
+            (TokenKind::Int, String::from("2")),
+            (TokenKind::RBracket, String::from("]")),
+            (TokenKind::Semicolon, String::from(";")),
+            (TokenKind::LBrace, String::from("{")),
+            (TokenKind::String, String::from("foo")),
+            (TokenKind::Colon, String::from(":")),
+            (TokenKind::String, String::from("bar")),
+            (TokenKind::RBrace, String::from("}")),
             (TokenKind::Eof, String::from("")),
         ];
 
         for test in tests.iter() {
             let _tok = lexer.next_token();
-            print!("{:?} \n", _tok);
+            println!("{:?} ", _tok);
             assert_eq!(_tok.token_kind, test.0);
             assert_eq!(_tok.literal, test.1);
         }
     }
+
+    #[test]
+    fn test_token_spans() {
+        let input = String::from("let x = 5;\n  x == \"ab\";\n");
+        let mut lexer = Lexer::new(input);
+
+        let tests = vec![
+            (TokenKind::Let, 0, 3, 1, 1),
+            (TokenKind::Ident, 4, 5, 1, 5),
+            (TokenKind::Assign, 6, 7, 1, 7),
+            (TokenKind::Int, 8, 9, 1, 9),
+            (TokenKind::Semicolon, 9, 10, 1, 10),
+            (TokenKind::Ident, 13, 14, 2, 3),
+            (TokenKind::Eq, 15, 17, 2, 5),
+            (TokenKind::String, 18, 22, 2, 8),
+            (TokenKind::Semicolon, 22, 23, 2, 12),
+            (TokenKind::Eof, 24, 24, 3, 1),
+        ];
+
+        for (token_kind, start, end, line, column) in tests {
+            let tok = lexer.next_token();
+            assert_eq!(tok.token_kind, token_kind);
+            assert_eq!(
+                tok.span,
+                Span {
+                    start,
+                    end,
+                    line,
+                    column
+                },
+                "{:?}",
+                tok
+            );
+        }
+    }
+
+    #[test]
+    fn test_string_tokens() {
+        let input = String::from(
+            r#""foobar" "foo bar" "a\nb\tc" "say \"hi\"" "back\\slash" "\u{41}\u{1F600}" """#,
+        );
+        let mut lexer = Lexer::new(input);
+
+        let tests = vec![
+            (TokenKind::String, "foobar"),
+            (TokenKind::String, "foo bar"),
+            (TokenKind::String, "a\nb\tc"),
+            (TokenKind::String, "say \"hi\""),
+            (TokenKind::String, "back\\slash"),
+            (TokenKind::String, "A\u{1F600}"),
+            (TokenKind::String, ""),
+            (TokenKind::Eof, ""),
+        ];
+
+        for (token_kind, literal) in tests {
+            let tok = lexer.next_token();
+            assert_eq!(tok.token_kind, token_kind);
+            assert_eq!(tok.literal, literal);
+        }
+    }
+
+    #[test]
+    fn test_malformed_strings() {
+        let tests = vec![
+            (r#""abc"#, "unterminated string literal"),
+            (r#""abc\"#, "unterminated string literal"),
+            (r#""a\qb""#, "unknown escape sequence: \\q"),
+            (
+                r#""\u41""#,
+                "invalid unicode escape: expected '{' after \\u",
+            ),
+            (
+                r#""\u{41""#,
+                "invalid unicode escape: expected 1 to 6 hex digits and '}'",
+            ),
+            (
+                r#""\u{D800}""#,
+                "invalid unicode escape: \"D800\" is not a character",
+            ),
+        ];
+
+        for (input, msg) in tests {
+            let mut lexer = Lexer::new(String::from(input));
+            let tok = lexer.next_token();
+            assert_eq!(tok.token_kind, TokenKind::Illegal, "{}", input);
+            assert_eq!(tok.literal, msg, "{}", input);
+            assert_eq!(lexer.next_token().token_kind, TokenKind::Eof, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_keywords() {
+        for (keyword, kind) in KEYWORDS {
+            let tok = Lexer::new(keyword.to_string()).next_token();
+            assert_eq!(tok.token_kind, kind, "{}", keyword);
+            assert_eq!(tok.literal, keyword);
+        }
+    }
+
+    #[test]
+    fn test_unicode_identifiers() {
+        let input = String::from("let größe = 1; let 名前 = \"日本\"; _x1 + café2;");
+        let mut lexer = Lexer::new(input);
+
+        let tests = vec![
+            (TokenKind::Let, "let"),
+            (TokenKind::Ident, "größe"),
+            (TokenKind::Assign, "="),
+            (TokenKind::Int, "1"),
+            (TokenKind::Semicolon, ";"),
+            (TokenKind::Let, "let"),
+            (TokenKind::Ident, "名前"),
+            (TokenKind::Assign, "="),
+            (TokenKind::String, "日本"),
+            (TokenKind::Semicolon, ";"),
+            (TokenKind::Ident, "_x1"),
+            (TokenKind::Plus, "+"),
+            (TokenKind::Ident, "café2"),
+            (TokenKind::Semicolon, ";"),
+            (TokenKind::Eof, ""),
+        ];
+
+        for (token_kind, literal) in tests {
+            let tok = lexer.next_token();
+            assert_eq!(tok.token_kind, token_kind);
+            assert_eq!(tok.literal, literal);
+        }
+    }
+
+    #[test]
+    fn test_multibyte_spans() {
+        let input = String::from("\"héllo\" + ñ;\n€");
+        let mut lexer = Lexer::new(input);
+
+        let tests = vec![
+            (TokenKind::String, 0, 8, 1, 1),
+            (TokenKind::Plus, 9, 10, 1, 9),
+            (TokenKind::Ident, 11, 13, 1, 11),
+            (TokenKind::Semicolon, 13, 14, 1, 12),
+            (TokenKind::Illegal, 15, 18, 2, 1),
+            (TokenKind::Eof, 18, 18, 2, 2),
+        ];
+
+        for (token_kind, start, end, line, column) in tests {
+            let tok = lexer.next_token();
+            assert_eq!(tok.token_kind, token_kind);
+            assert_eq!(
+                tok.span,
+                Span {
+                    start,
+                    end,
+                    line,
+                    column
+                },
+                "{:?}",
+                tok
+            );
+        }
+    }
+
+    #[test]
+    fn test_illegal_characters() {
+        for (input, msg) in [
+            ("€", "illegal character '€'"),
+            ("@", "illegal character '@'"),
+            ("1\u{0}", "illegal character '\\0'"),
+        ] {
+            let mut lexer = Lexer::new(String::from(input));
+            let mut tok = lexer.next_token();
+            while tok.token_kind != TokenKind::Illegal {
+                assert_ne!(tok.token_kind, TokenKind::Eof, "{}", input);
+                tok = lexer.next_token();
+            }
+            assert_eq!(tok.literal, msg, "{}", input);
+            assert_eq!(lexer.next_token().token_kind, TokenKind::Eof, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_comments() {
+        let input = "// größe des Fensters\nlet a = 10 / 2; // halbiert\n/* outer /* inner */ still outer */ a /* 日本語 */ * 3;";
+
+        let tests = vec![
+            (TokenKind::Comment, "// größe des Fensters"),
+            (TokenKind::Let, "let"),
+            (TokenKind::Ident, "a"),
+            (TokenKind::Assign, "="),
+            (TokenKind::Int, "10"),
+            (TokenKind::Slash, "/"),
+            (TokenKind::Int, "2"),
+            (TokenKind::Semicolon, ";"),
+            (TokenKind::Comment, "// halbiert"),
+            (TokenKind::Comment, "/* outer /* inner */ still outer */"),
+            (TokenKind::Ident, "a"),
+            (TokenKind::Comment, "/* 日本語 */"),
+            (TokenKind::Asterisk, "*"),
+            (TokenKind::Int, "3"),
+            (TokenKind::Semicolon, ";"),
+            (TokenKind::Eof, ""),
+        ];
+
+        let mut lexer = Lexer::new(String::from(input)).with_comments(true);
+        for (token_kind, literal) in tests.iter() {
+            let tok = lexer.next_token();
+            assert_eq!(&tok.token_kind, token_kind);
+            assert_eq!(tok.literal, *literal);
+            if tok.token_kind == TokenKind::Comment {
+                assert_eq!(&input[tok.span.start..tok.span.end], tok.literal);
+            }
+        }
+
+        let mut lexer = Lexer::new(String::from(input));
+        for (token_kind, literal) in tests.iter().filter(|(k, _)| *k != TokenKind::Comment) {
+            let tok = lexer.next_token();
+            assert_eq!(&tok.token_kind, token_kind);
+            assert_eq!(tok.literal, *literal);
+        }
+    }
+
+    #[test]
+    fn test_unterminated_block_comment() {
+        let mut lexer = Lexer::new(String::from("1 /* a /* b */\n2"));
+
+        assert_eq!(lexer.next_token().token_kind, TokenKind::Int);
+        let tok = lexer.next_token();
+        assert_eq!(tok.token_kind, TokenKind::Illegal);
+        assert_eq!(tok.literal, "unterminated block comment");
+        assert_eq!(
+            tok.span,
+            Span {
+                start: 2,
+                end: 16,
+                line: 1,
+                column: 3
+            }
+        );
+        assert_eq!(lexer.next_token().token_kind, TokenKind::Eof);
+    }
 }
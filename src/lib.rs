@@ -0,0 +1,39 @@
+//! Monkey, an interpreted language from "Writing An Interpreter In Go",
+//! written in Rust.
+//!
+//! The [`Interpreter`] is the easiest way to run Monkey code from a Rust
+//! program:
+//!
+//! ```
+//! use monkey_rs::{Interpreter, Value};
+//!
+//! let mut interpreter = Interpreter::new();
+//! interpreter.eval_str("let double = fn(x) { x * 2 };").unwrap();
+//! assert_eq!(interpreter.eval_str("double(21)"), Ok(Value::Integer(42)));
+//! ```
+//!
+//! The [`Lexer`], [`Parser`] and evaluator stages are public as well, for
+//! tools that need the tokens or the [`Program`] itself.
+
+pub mod ast;
+pub mod builtins;
+pub mod code;
+pub mod compiler;
+pub mod cst;
+pub mod diagnostic;
+pub mod disasm;
+pub mod environment;
+pub mod evaluator;
+pub mod formatter;
+pub mod interpreter;
+pub mod lexer;
+pub mod mkc;
+pub mod object;
+pub mod parser;
+pub mod token;
+pub mod vm;
+
+pub use ast::Program;
+pub use interpreter::{Error, Interpreter, Value};
+pub use lexer::Lexer;
+pub use parser::Parser;
@@ -1,11 +1,65 @@
-mod ast;
-mod lexer;
-mod parser;
+use std::{env, process, thread};
+
+mod commands;
+mod editor;
+mod inspect;
 mod repl;
-mod token;
+
+const USAGE: &str = "usage: monkey [COMMAND] [ARGS]...
+
+commands:
+    run FILE        evaluate a script (--vm: on the bytecode VM) or run a .mkc file
+    compile FILE    compile a script to a .mkc file (-o OUT to name it)
+    lex FILE        print the tokens of a script
+    parse FILE      print the statements of a script as parsed
+    disasm FILE     print the bytecode a script compiles to
+    check FILE...   report syntax errors without running anything
+    fmt FILE...     format scripts; see monkey fmt --help
+    repl            start an interactive session (the default)
+
+FILE may be - to read standard input. The exit status is 0 on success,
+65 for syntax errors or invalid bytecode and 70 for runtime errors.";
+
+// The evaluator recurses on the native stack, so commands run on a thread
+// with room for `evaluator::MAX_CALL_DEPTH` calls instead of on the main
+// thread's few megabytes. Only the pages in use take memory.
+const STACK_SIZE: usize = 1 << 30;
 
 fn main() {
-    println!("Hello! This is the Monkey programming language!");
-    println!("Feel free to type in commands");
-    repl::start();
+    let worker = thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(run)
+        .expect("cannot start the interpreter thread");
+    // A panic has already been reported; exit as Rust does for one.
+    let status = worker.join().unwrap_or(101);
+    process::exit(status);
+}
+
+fn run() -> i32 {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let rest = args.get(1..).unwrap_or_default();
+
+    match args.first().map(String::as_str) {
+        None | Some("repl") => {
+            println!("Hello! This is the Monkey programming language!");
+            println!("Feel free to type in commands");
+            repl::start();
+            0
+        }
+        Some("run") => commands::run(rest),
+        Some("compile") => commands::compile(rest),
+        Some("lex") => commands::lex(rest),
+        Some("parse") => commands::parse(rest),
+        Some("disasm") => commands::disasm(rest),
+        Some("check") => commands::check(rest),
+        Some("fmt") => commands::fmt(rest),
+        Some("-h" | "--help" | "help") => {
+            println!("{}", USAGE);
+            0
+        }
+        Some(command) => {
+            eprintln!("monkey: unknown command {}\n\n{}", command, USAGE);
+            commands::EXIT_USAGE
+        }
+    }
 }
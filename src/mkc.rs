@@ -0,0 +1,595 @@
+//! The `.mkc` file format: [`Bytecode`] saved to run later without its
+//! source.
+//!
+//! All integers are big-endian, like instruction operands. A file is
+//!
+//! ```text
+//! magic      b"\0MKC"
+//! version    u16
+//! globals    u32 count, then each name as a string
+//! constants  u32 count, then each as a tag byte and a value:
+//!            0 an integer (i64), 1 a string, 2 a function
+//! main       a function
+//! ```
+//!
+//! where a string is its length in bytes (u32) followed by UTF-8, and a
+//! function is its number of parameters (u32), its locals (u32 count and
+//! strings), its source (a string), its instructions (u32 length and
+//! bytes) and its line table (u32 count of u32 offset and line pairs).
+//!
+//! [`decode`] checks everything that the [vm](crate::vm) takes for granted
+//! in code from the compiler, so that a corrupt file is an error rather
+//! than a crash.
+
+use std::rc::Rc;
+
+use crate::{
+    code::{Instructions, Opcode},
+    compiler::Bytecode,
+    object::{CompiledFunction, Object},
+};
+
+pub const MAGIC: &[u8; 4] = b"\0MKC";
+
+/// The version of the format written by [`encode`], and the only one
+/// [`decode`] accepts.
+pub const VERSION: u16 = 1;
+
+const INTEGER: u8 = 0;
+const STRING: u8 = 1;
+const FUNCTION: u8 = 2;
+
+/// Returns whether `bytes` start like a `.mkc` file.
+pub fn is_mkc(bytes: &[u8]) -> bool {
+    bytes.starts_with(MAGIC)
+}
+
+/// Serializes `bytecode`.
+///
+/// # Panics
+///
+/// If the constant pool holds anything but the integers, strings and
+/// functions that the compiler puts there.
+pub fn encode(bytecode: &Bytecode) -> Vec<u8> {
+    let mut out = Vec::from(&MAGIC[..]);
+    out.extend_from_slice(&VERSION.to_be_bytes());
+
+    write_len(&mut out, bytecode.globals.len());
+    for global in bytecode.globals.iter() {
+        write_str(&mut out, global);
+    }
+
+    write_len(&mut out, bytecode.constants.len());
+    for constant in bytecode.constants.iter() {
+        match constant {
+            Object::Integer(value) => {
+                out.push(INTEGER);
+                out.extend_from_slice(&value.to_be_bytes());
+            }
+            Object::String(value) => {
+                out.push(STRING);
+                write_str(&mut out, value);
+            }
+            Object::CompiledFunction(function) => {
+                out.push(FUNCTION);
+                write_function(&mut out, function);
+            }
+            obj => panic!("cannot encode a {} constant", obj.type_name()),
+        }
+    }
+
+    write_function(&mut out, &bytecode.main);
+    out
+}
+
+fn write_function(out: &mut Vec<u8>, function: &CompiledFunction) {
+    write_len(out, function.num_parameters);
+    write_len(out, function.locals.len());
+    for local in function.locals.iter() {
+        write_str(out, local);
+    }
+    write_str(out, &function.source);
+    write_len(out, function.instructions.len());
+    out.extend_from_slice(&function.instructions);
+    write_len(out, function.lines.len());
+    for &(offset, line) in function.lines.iter() {
+        write_len(out, offset);
+        write_len(out, line);
+    }
+}
+
+fn write_len(out: &mut Vec<u8>, len: usize) {
+    out.extend_from_slice(&(len as u32).to_be_bytes());
+}
+
+fn write_str(out: &mut Vec<u8>, s: &str) {
+    write_len(out, s.len());
+    out.extend_from_slice(s.as_bytes());
+}
+
+/// Loads bytecode written by [`encode`], failing with a message on input
+/// that is truncated, corrupt, or from another version of the format.
+pub fn decode(bytes: &[u8]) -> Result<Bytecode, String> {
+    let mut reader = Reader { bytes, pos: 0 };
+
+    if !is_mkc(bytes) {
+        return Err(String::from("not a .mkc file"));
+    }
+    reader.pos = MAGIC.len();
+    let version = reader.u16()?;
+    if version != VERSION {
+        return Err(format!(
+            "unsupported .mkc version {} (expected {})",
+            version, VERSION
+        ));
+    }
+
+    let mut globals = vec![];
+    for _ in 0..reader.len()? {
+        globals.push(reader.string()?);
+    }
+
+    let mut constants = vec![];
+    for _ in 0..reader.len()? {
+        let pos = reader.pos;
+        let constant = match reader.u8()? {
+            INTEGER => Object::Integer(i64::from_be_bytes(reader.array()?)),
+            STRING => Object::String(reader.string()?),
+            FUNCTION => Object::CompiledFunction(Rc::new(reader.function()?)),
+            tag => return Err(format!("byte {}: unknown constant tag {}", pos, tag)),
+        };
+        constants.push(constant);
+    }
+
+    let main = reader.function()?;
+    if reader.pos != bytes.len() {
+        return Err(format!(
+            "byte {}: unexpected data after the code",
+            reader.pos
+        ));
+    }
+
+    let bytecode = Bytecode {
+        main: Rc::new(main),
+        constants,
+        globals,
+    };
+    validate(&bytecode)?;
+    Ok(bytecode)
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl Reader<'_> {
+    fn take(&mut self, n: usize) -> Result<&[u8], String> {
+        match self.bytes.get(self.pos..self.pos.saturating_add(n)) {
+            Some(taken) => {
+                self.pos += n;
+                Ok(taken)
+            }
+            None => Err(format!("byte {}: unexpected end of file", self.bytes.len())),
+        }
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], String> {
+        Ok(self.take(N)?.try_into().unwrap())
+    }
+
+    fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, String> {
+        Ok(u16::from_be_bytes(self.array()?))
+    }
+
+    fn len(&mut self) -> Result<usize, String> {
+        Ok(u32::from_be_bytes(self.array()?) as usize)
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        let len = self.len()?;
+        let pos = self.pos;
+        String::from_utf8(self.take(len)?.to_vec())
+            .map_err(|_| format!("byte {}: string is not valid UTF-8", pos))
+    }
+
+    fn function(&mut self) -> Result<CompiledFunction, String> {
+        let num_parameters = self.len()?;
+        let mut locals = vec![];
+        for _ in 0..self.len()? {
+            locals.push(self.string()?);
+        }
+        let source = self.string()?;
+        let len = self.len()?;
+        let instructions = Instructions(self.take(len)?.to_vec());
+        let mut lines = vec![];
+        for _ in 0..self.len()? {
+            lines.push((self.len()?, self.len()?));
+        }
+
+        Ok(CompiledFunction {
+            instructions,
+            num_parameters,
+            locals,
+            source,
+            lines,
+        })
+    }
+}
+
+/// Checks that `bytecode` can run without the VM indexing out of bounds:
+/// that every instruction decodes, its operands refer to constants, globals
+/// and locals that exist, jumps land on instructions, the stack never
+/// underflows, and functions only read the locals of functions they are
+/// nested in.
+fn validate(bytecode: &Bytecode) -> Result<(), String> {
+    // Function 0 is the top level; the others are the function constants.
+    let mut functions = vec![(String::from("main"), &*bytecode.main)];
+    let mut ids = vec![None; bytecode.constants.len()];
+    for (index, constant) in bytecode.constants.iter().enumerate() {
+        if let Object::CompiledFunction(function) = constant {
+            ids[index] = Some(functions.len());
+            functions.push((format!("function {}", index), function));
+        }
+    }
+    if bytecode.main.num_parameters != 0 || !bytecode.main.locals.is_empty() {
+        return Err(String::from("main: the top level cannot have locals"));
+    }
+
+    // The function whose code creates each function, by id.
+    let mut creators = vec![None; functions.len()];
+    for (id, (name, function)) in functions.iter().enumerate() {
+        check_function(bytecode, function, id, &ids, &mut creators)
+            .map_err(|err| format!("{}: {}", name, err))?;
+    }
+    for (id, (name, function)) in functions.iter().enumerate() {
+        check_outer_reads(&functions, &creators, id, function)
+            .map_err(|err| format!("{}: {}", name, err))?;
+    }
+    Ok(())
+}
+
+fn check_function(
+    bytecode: &Bytecode,
+    function: &CompiledFunction,
+    id: usize,
+    ids: &[Option<usize>],
+    creators: &mut [Option<usize>],
+) -> Result<(), String> {
+    let instructions = &function.instructions;
+    if function.num_parameters > function.locals.len() {
+        return Err(String::from("more parameters than locals"));
+    }
+
+    // Decode everything first, so that jumps can be checked against where
+    // instructions start.
+    let mut decoded = vec![None; instructions.len()];
+    let mut offset = 0;
+    while offset < instructions.len() {
+        let (op, operands) = instructions
+            .instruction_at(offset)
+            .map_err(|err| format!("offset {}: {}", offset, err))?;
+        let width = op.width();
+        decoded[offset] = Some((op, operands));
+        offset += width;
+    }
+
+    for (offset, instruction) in decoded.iter().enumerate() {
+        let (op, operands) = match instruction {
+            Some(instruction) => instruction,
+            None => continue,
+        };
+        let valid = match op {
+            Opcode::Constant => operands[0] < bytecode.constants.len(),
+            // A function created in more than one place could be nested in
+            // functions with different locals.
+            Opcode::Closure => match ids.get(operands[0]).copied().flatten() {
+                Some(inner) if creators[inner].is_none() => {
+                    creators[inner] = Some(id);
+                    true
+                }
+                _ => false,
+            },
+            Opcode::GetGlobal | Opcode::SetGlobal => operands[0] < bytecode.globals.len(),
+            Opcode::GetLocal | Opcode::SetLocal => operands[0] < function.locals.len(),
+            Opcode::Jump | Opcode::JumpNotTruthy => {
+                decoded.get(operands[0]).is_some_and(Option::is_some)
+            }
+            _ => true,
+        };
+        if !valid {
+            return Err(format!(
+                "offset {}: bad operand in {}",
+                offset,
+                op.definition().name
+            ));
+        }
+    }
+
+    check_stack(&decoded).map_err(|(offset, err)| format!("offset {}: {}", offset, err))?;
+
+    let mut last = 0;
+    for &(offset, line) in function.lines.iter() {
+        if line == 0 || offset < last || offset >= instructions.len() {
+            return Err(String::from("corrupt line table"));
+        }
+        last = offset;
+    }
+    Ok(())
+}
+
+/// Checks that the locals `OpGetOuter` reads exist in the functions that
+/// the reading function is nested in.
+fn check_outer_reads(
+    functions: &[(String, &CompiledFunction)],
+    creators: &[Option<usize>],
+    id: usize,
+    function: &CompiledFunction,
+) -> Result<(), String> {
+    let instructions = &function.instructions;
+    let mut offset = 0;
+
+    while offset < instructions.len() {
+        // Already decoded once, so this cannot fail.
+        let (op, operands) = instructions.instruction_at(offset)?;
+        if op == Opcode::GetOuter {
+            let (depth, slot) = (operands[0], operands[1]);
+            let outer = (0..depth).try_fold(id, |outer, _| creators[outer]);
+            // The top level has no locals to read.
+            let valid =
+                outer.is_some_and(|outer| outer != 0 && slot < functions[outer].1.locals.len());
+            if !valid {
+                return Err(format!("offset {}: bad operand in OpGetOuter", offset));
+            }
+        }
+        offset += op.width();
+    }
+    Ok(())
+}
+
+/// Follows every path through a function, checking that each instruction
+/// finds the operands it pops, that paths meet with the same stack height,
+/// and that none runs past the end of the code.
+fn check_stack(decoded: &[Option<(Opcode, Vec<usize>)>]) -> Result<(), (usize, String)> {
+    let mut heights: Vec<Option<usize>> = vec![None; decoded.len()];
+    let mut pending = vec![(0, 0)];
+
+    while let Some((offset, height)) = pending.pop() {
+        let (op, operands) = match decoded.get(offset) {
+            Some(Some(instruction)) => instruction,
+            _ => return Err((offset, String::from("code runs past its end"))),
+        };
+        match heights[offset] {
+            Some(seen) if seen == height => continue,
+            Some(seen) => {
+                return Err((
+                    offset,
+                    format!("stack height {} here, but {} on another path", height, seen),
+                ))
+            }
+            None => heights[offset] = Some(height),
+        }
+
+        let (pops, pushes) = match op {
+            Opcode::Constant
+            | Opcode::True
+            | Opcode::False
+            | Opcode::Null
+            | Opcode::GetGlobal
+            | Opcode::GetLocal
+            | Opcode::GetOuter
+            | Opcode::Closure => (0, 1),
+            Opcode::Pop | Opcode::SetGlobal | Opcode::SetLocal | Opcode::JumpNotTruthy => (1, 0),
+            Opcode::Add
+            | Opcode::Sub
+            | Opcode::Mul
+            | Opcode::Div
+            | Opcode::Equal
+            | Opcode::NotEqual
+            | Opcode::GreaterThan
+            | Opcode::LessThan
+            | Opcode::Index => (2, 1),
+            Opcode::Minus | Opcode::Bang | Opcode::HashKey | Opcode::ReturnValue => (1, 1),
+            Opcode::Jump => (0, 0),
+            Opcode::Array => (operands[0], 1),
+            Opcode::Hash => (2 * operands[0], 1),
+            Opcode::Call => (operands[0] + 1, 1),
+        };
+        if height < pops {
+            return Err((
+                offset,
+                format!("{} pops more than the stack holds", op.definition().name),
+            ));
+        }
+        let height = height - pops + pushes;
+
+        let next = offset + op.width();
+        match op {
+            Opcode::ReturnValue => {}
+            Opcode::Jump => pending.push((operands[0], height)),
+            Opcode::JumpNotTruthy => {
+                pending.push((operands[0], height));
+                pending.push((next, height));
+            }
+            _ => pending.push((next, height)),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use crate::code::{make, Instructions, Opcode};
+    use crate::compiler::{Bytecode, Compiler};
+    use crate::lexer::Lexer;
+    use crate::mkc::{decode, encode};
+    use crate::object::{CompiledFunction, Object};
+    use crate::parser::Parser;
+    use crate::vm::Vm;
+
+    fn compile(input: &str) -> Bytecode {
+        let mut p = Parser::new(Lexer::new(input.to_string()));
+        let program = p.parse_program();
+        assert!(p.errors.is_empty(), "parser errors: {:?}", p.errors);
+        Compiler::new().compile(&program).unwrap()
+    }
+
+    fn function(instructions: Vec<Vec<u8>>, locals: &[&str]) -> CompiledFunction {
+        CompiledFunction {
+            instructions: Instructions(instructions.concat()),
+            num_parameters: 0,
+            locals: locals.iter().map(|l| l.to_string()).collect(),
+            source: String::new(),
+            lines: vec![],
+        }
+    }
+
+    fn bytecode(main: Vec<Vec<u8>>, constants: Vec<Object>) -> Bytecode {
+        Bytecode {
+            main: Rc::new(function(main, &[])),
+            constants,
+            globals: vec![String::from("x")],
+        }
+    }
+
+    const INPUT: &str = "let s = \"hi\";\nlet f = fn(n) {\n  let g = fn() { n * 2 };\n  if (n > 1) { g() } else { len(s) }\n};\n[f(5), f(-1)]";
+
+    #[test]
+    fn test_round_trip() {
+        let bytecode = compile(INPUT);
+        let decoded = decode(&encode(&bytecode)).unwrap();
+        assert_eq!(decoded, bytecode);
+        assert_eq!(
+            Vm::new().run(&decoded).map(|obj| obj.inspect()),
+            Ok(String::from("[10, 2]"))
+        );
+    }
+
+    #[test]
+    fn test_truncated() {
+        let bytes = encode(&compile(INPUT));
+        for len in 0..bytes.len() {
+            assert!(decode(&bytes[..len]).is_err(), "decoded {} bytes", len);
+        }
+    }
+
+    #[test]
+    fn test_invalid() {
+        let valid = encode(&compile("1"));
+        let mut trailing = valid.clone();
+        trailing.push(0);
+        let mut version = valid.clone();
+        version[5] = 9;
+
+        let tests = vec![
+            (b"monkey".to_vec(), "not a .mkc file"),
+            (version, "unsupported .mkc version 9 (expected 1)"),
+            (trailing, "byte 55: unexpected data after the code"),
+            (
+                encode(&bytecode(vec![vec![200]], vec![])),
+                "main: offset 0: unknown opcode 200",
+            ),
+            (
+                encode(&bytecode(vec![vec![Opcode::Constant as u8, 0]], vec![])),
+                "main: offset 0: truncated OpConstant",
+            ),
+            (
+                encode(&bytecode(
+                    vec![make(Opcode::Constant, &[1]), make(Opcode::ReturnValue, &[])],
+                    vec![Object::Integer(1)],
+                )),
+                "main: offset 0: bad operand in OpConstant",
+            ),
+            (
+                encode(&bytecode(
+                    vec![
+                        make(Opcode::GetGlobal, &[1]),
+                        make(Opcode::ReturnValue, &[]),
+                    ],
+                    vec![],
+                )),
+                "main: offset 0: bad operand in OpGetGlobal",
+            ),
+            (
+                encode(&bytecode(
+                    vec![
+                        make(Opcode::True, &[]),
+                        make(Opcode::JumpNotTruthy, &[2]),
+                        make(Opcode::Null, &[]),
+                        make(Opcode::ReturnValue, &[]),
+                    ],
+                    vec![],
+                )),
+                "main: offset 1: bad operand in OpJumpNotTruthy",
+            ),
+            (
+                encode(&bytecode(
+                    vec![make(Opcode::Closure, &[0]), make(Opcode::ReturnValue, &[])],
+                    vec![Object::Integer(1)],
+                )),
+                "main: offset 0: bad operand in OpClosure",
+            ),
+            (
+                encode(&bytecode(
+                    vec![make(Opcode::Add, &[]), make(Opcode::ReturnValue, &[])],
+                    vec![],
+                )),
+                "main: offset 0: OpAdd pops more than the stack holds",
+            ),
+            (
+                encode(&bytecode(vec![make(Opcode::Null, &[])], vec![])),
+                "main: offset 1: code runs past its end",
+            ),
+            (
+                encode(&bytecode(
+                    vec![
+                        make(Opcode::True, &[]),
+                        make(Opcode::JumpNotTruthy, &[5]),
+                        make(Opcode::Null, &[]),
+                        make(Opcode::Null, &[]),
+                        make(Opcode::ReturnValue, &[]),
+                    ],
+                    vec![],
+                )),
+                "main: offset 5: stack height 0 here, but 1 on another path",
+            ),
+            (
+                encode(&bytecode(
+                    vec![make(Opcode::Closure, &[0]), make(Opcode::ReturnValue, &[])],
+                    vec![Object::CompiledFunction(Rc::new(function(
+                        vec![
+                            make(Opcode::GetOuter, &[1, 0]),
+                            make(Opcode::ReturnValue, &[]),
+                        ],
+                        &["a"],
+                    )))],
+                )),
+                "function 0: offset 0: bad operand in OpGetOuter",
+            ),
+            (
+                encode(&bytecode(
+                    vec![
+                        make(Opcode::Closure, &[0]),
+                        make(Opcode::Closure, &[0]),
+                        make(Opcode::ReturnValue, &[]),
+                    ],
+                    vec![Object::CompiledFunction(Rc::new(function(
+                        vec![make(Opcode::Null, &[]), make(Opcode::ReturnValue, &[])],
+                        &[],
+                    )))],
+                )),
+                "main: offset 3: bad operand in OpClosure",
+            ),
+        ];
+
+        for (bytes, expected) in tests {
+            assert_eq!(decode(&bytes), Err(String::from(expected)));
+        }
+    }
+}
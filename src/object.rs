@@ -0,0 +1,197 @@
+use std::{collections::BTreeMap, fmt, rc::Rc};
+
+use crate::{
+    ast::{BlockStatement, Identifier, Node},
+    code::Instructions,
+    environment::Environment,
+    vm::Scope,
+};
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Object {
+    Integer(i64),
+    Boolean(bool),
+    String(String),
+    Null,
+    ReturnValue(Box<Object>),
+    Error(String),
+    Function(Rc<Function>),
+    Builtin(Rc<Builtin>),
+    CompiledFunction(Rc<CompiledFunction>),
+    Closure(Rc<Closure>),
+    Array(Vec<Object>),
+    Hash(BTreeMap<HashKey, HashPair>),
+}
+
+/// The identity of a hashable object: two objects produce the same key
+/// exactly when they are equal.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone)]
+pub enum HashKey {
+    Integer(i64),
+    Boolean(bool),
+    String(String),
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct HashPair {
+    pub key: Object,
+    pub value: Object,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Function {
+    pub parameters: Vec<Identifier>,
+    pub body: BlockStatement,
+    pub env: Environment,
+}
+
+/// A function literal compiled to bytecode. It only appears in the constant
+/// pool; running it creates a [`Closure`].
+#[derive(Debug, PartialEq)]
+pub struct CompiledFunction {
+    pub instructions: Instructions,
+    pub num_parameters: usize,
+    /// The names of the function's locals, indexed by slot. The parameters
+    /// come first.
+    pub locals: Vec<String>,
+    /// The function literal as the evaluator prints it, for `inspect`.
+    pub source: String,
+    /// The source line of the instructions: each entry is the offset of
+    /// the first instruction compiled from a line, and that line.
+    pub lines: Vec<(usize, usize)>,
+}
+
+impl CompiledFunction {
+    /// Returns the source line of the instruction at `offset`.
+    pub fn line(&self, offset: usize) -> Option<usize> {
+        let entry = self.lines.partition_point(|&(start, _)| start <= offset);
+        entry.checked_sub(1).map(|entry| self.lines[entry].1)
+    }
+}
+
+/// A compiled function together with the locals of the call that created
+/// it, which it shares rather than copies.
+#[derive(Debug, PartialEq)]
+pub struct Closure {
+    pub function: Rc<CompiledFunction>,
+    /// `None` for a function created outside any other function.
+    pub scope: Option<Rc<Scope>>,
+}
+
+pub type BuiltinFunction = dyn Fn(&[Object]) -> Object;
+
+/// How many arguments a builtin accepts.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Arity {
+    Exact(usize),
+    Variadic,
+}
+
+pub struct Builtin {
+    pub name: String,
+    pub arity: Arity,
+    pub func: Box<BuiltinFunction>,
+}
+
+impl Builtin {
+    pub fn call(&self, args: &[Object]) -> Object {
+        match self.arity {
+            Arity::Exact(want) if want != args.len() => Object::Error(format!(
+                "wrong number of arguments: want={}, got={}",
+                want,
+                args.len()
+            )),
+            _ => (self.func)(args),
+        }
+    }
+}
+
+// Builtins are identified by name; closures cannot be compared.
+impl PartialEq for Builtin {
+    fn eq(&self, other: &Builtin) -> bool {
+        self.name == other.name
+    }
+}
+
+impl fmt::Debug for Builtin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Builtin")
+            .field("name", &self.name)
+            .field("arity", &self.arity)
+            .finish()
+    }
+}
+
+pub const TRUE: Object = Object::Boolean(true);
+pub const FALSE: Object = Object::Boolean(false);
+pub const NULL: Object = Object::Null;
+
+impl Object {
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Object::Integer(_) => "INTEGER",
+            Object::Boolean(_) => "BOOLEAN",
+            Object::String(_) => "STRING",
+            Object::Null => "NULL",
+            Object::ReturnValue(_) => "RETURN_VALUE",
+            Object::Error(_) => "ERROR",
+            Object::Function(_) => "FUNCTION",
+            Object::Builtin(_) => "BUILTIN",
+            Object::CompiledFunction(_) => "COMPILED_FUNCTION",
+            // Closures are what the evaluator calls functions.
+            Object::Closure(_) => "FUNCTION",
+            Object::Array(_) => "ARRAY",
+            Object::Hash(_) => "HASH",
+        }
+    }
+
+    pub fn inspect(&self) -> String {
+        match self {
+            Object::Integer(value) => value.to_string(),
+            Object::Boolean(value) => value.to_string(),
+            Object::String(value) => value.clone(),
+            Object::Null => String::from("null"),
+            Object::ReturnValue(value) => value.inspect(),
+            Object::Error(message) => format!("ERROR: {}", message),
+            Object::Function(function) => {
+                let params: Vec<String> = function.parameters.iter().map(|p| p.string()).collect();
+                format!("fn({}) {}", params.join(", "), function.body.string())
+            }
+            Object::Builtin(_) => String::from("builtin function"),
+            Object::CompiledFunction(function) => function.source.clone(),
+            Object::Closure(closure) => closure.function.source.clone(),
+            Object::Array(elements) => {
+                let elements: Vec<String> = elements.iter().map(|e| e.inspect()).collect();
+                format!("[{}]", elements.join(", "))
+            }
+            Object::Hash(pairs) => {
+                let pairs: Vec<String> = pairs
+                    .values()
+                    .map(|pair| format!("{}: {}", pair.key.inspect(), pair.value.inspect()))
+                    .collect();
+                format!("{{{}}}", pairs.join(", "))
+            }
+        }
+    }
+
+    /// Returns the key this object is stored under in a hash, or `None`
+    /// if the object cannot be used as a hash key.
+    pub fn hash_key(&self) -> Option<HashKey> {
+        match self {
+            Object::Integer(value) => Some(HashKey::Integer(*value)),
+            Object::Boolean(value) => Some(HashKey::Boolean(*value)),
+            Object::String(value) => Some(HashKey::String(value.clone())),
+            _ => None,
+        }
+    }
+
+    pub fn is_error(&self) -> bool {
+        matches!(self, Object::Error(_))
+    }
+
+    /// Returns whether evaluation has to stop and hand this object up: an
+    /// error, or the value of a `return` on its way out of a function.
+    pub fn is_error_or_return(&self) -> bool {
+        matches!(self, Object::Error(_) | Object::ReturnValue(_))
+    }
+}
@@ -1,24 +1,69 @@
+use std::collections::HashMap;
+
 use crate::{
     ast::{
-        Expression, ExpressionStatement, Identifier, LetStatement, Program, ReturnStatement,
-        Statement,
+        ArrayLiteral, BlockStatement, Boolean, CallExpression, ErrorStatement, Expression,
+        ExpressionStatement, FunctionLiteral, HashLiteral, Identifier, IfExpression,
+        IndexExpression, InfixExpression, IntegerLiteral, LetStatement, PrefixExpression, Program,
+        ReturnStatement, Statement, StringLiteral,
     },
+    diagnostic::Diagnostic,
     lexer::Lexer,
     parser::Precedence::Lowest,
-    token::{Token, TokenKind},
+    token::{Span, Token, TokenKind},
 };
 
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
 pub enum Precedence {
     Lowest,
+    Equals,      // ==
+    LessGreater, // > or <
+    Sum,         // +
+    Product,     // *
+    Prefix,      // -X or !X
+    Call,        // myFunction(X)
+    Index,       // array[index]
+}
+
+pub(crate) fn precedence_of(t: &TokenKind) -> Precedence {
+    match t {
+        TokenKind::Eq | TokenKind::NotEq => Precedence::Equals,
+        TokenKind::Lt | TokenKind::Gt => Precedence::LessGreater,
+        TokenKind::Plus | TokenKind::Minus => Precedence::Sum,
+        TokenKind::Slash | TokenKind::Asterisk => Precedence::Product,
+        TokenKind::LParen => Precedence::Call,
+        TokenKind::LBracket => Precedence::Index,
+        _ => Precedence::Lowest,
+    }
 }
 
+/// How deeply expressions may nest, counting every parenthesis, operand,
+/// literal and function body that encloses them. Parsing, evaluation and
+/// compilation all recurse once per level, so deeper input is rejected
+/// before it can exhaust the native stack.
+pub const MAX_NESTING: usize = 256;
+
+type PrefixParseFn = fn(&mut Parser) -> Option<Expression>;
+type InfixParseFn = fn(&mut Parser, Expression) -> Option<Expression>;
+
 pub struct Parser {
     l: Lexer,
 
-    pub errors: Vec<String>,
+    pub errors: Vec<Diagnostic>,
 
     cur_token: Token,
     peek_token: Token,
+
+    // How many braces are open before cur_token, and where the token before
+    // it ended; both are used to find statement boundaries when recovering.
+    depth: usize,
+    last_end: usize,
+
+    // How many calls to parse_expression are under way.
+    nesting: usize,
+
+    prefix_parse_fns: HashMap<TokenKind, PrefixParseFn>,
+    infix_parse_fns: HashMap<TokenKind, InfixParseFn>,
 }
 
 impl Parser {
@@ -31,46 +76,148 @@ impl Parser {
             cur_token: Token {
                 token_kind: TokenKind::Default,
                 literal: String::from(""),
+                span: Span::default(),
             },
             peek_token: Token {
                 token_kind: TokenKind::Default,
                 literal: String::from(""),
+                span: Span::default(),
             },
+
+            depth: 0,
+            last_end: 0,
+
+            nesting: 0,
+
+            prefix_parse_fns: HashMap::new(),
+            infix_parse_fns: HashMap::new(),
         };
+
+        p.register_prefix(TokenKind::Ident, Parser::parse_identifier);
+        p.register_prefix(TokenKind::Int, Parser::parse_integer_literal);
+        p.register_prefix(TokenKind::String, Parser::parse_string_literal);
+        p.register_prefix(TokenKind::Illegal, Parser::parse_illegal);
+        p.register_prefix(TokenKind::Bang, Parser::parse_prefix_expression);
+        p.register_prefix(TokenKind::Minus, Parser::parse_prefix_expression);
+        p.register_prefix(TokenKind::True, Parser::parse_boolean);
+        p.register_prefix(TokenKind::False, Parser::parse_boolean);
+        p.register_prefix(TokenKind::LParen, Parser::parse_grouped_expression);
+        p.register_prefix(TokenKind::If, Parser::parse_if_expression);
+        p.register_prefix(TokenKind::Function, Parser::parse_function_literal);
+        p.register_prefix(TokenKind::LBracket, Parser::parse_array_literal);
+        // Blocks are only ever parsed directly by `if` and `fn`, so a `{`
+        // reached through parse_expression always opens a hash literal.
+        p.register_prefix(TokenKind::LBrace, Parser::parse_hash_literal);
+
+        for t in [
+            TokenKind::Plus,
+            TokenKind::Minus,
+            TokenKind::Slash,
+            TokenKind::Asterisk,
+            TokenKind::Eq,
+            TokenKind::NotEq,
+            TokenKind::Lt,
+            TokenKind::Gt,
+        ] {
+            p.register_infix(t, Parser::parse_infix_expression);
+        }
+        p.register_infix(TokenKind::LParen, Parser::parse_call_expression);
+        p.register_infix(TokenKind::LBracket, Parser::parse_index_expression);
+
         // Read two tokens, so curToken and peekToken are both set.
         p.next_token();
         p.next_token();
         p
     }
 
+    fn register_prefix(&mut self, t: TokenKind, f: PrefixParseFn) {
+        self.prefix_parse_fns.insert(t, f);
+    }
+
+    fn register_infix(&mut self, t: TokenKind, f: InfixParseFn) {
+        self.infix_parse_fns.insert(t, f);
+    }
+
     fn next_token(&mut self) {
+        match self.cur_token.token_kind {
+            TokenKind::LBrace => self.depth += 1,
+            TokenKind::RBrace => self.depth = self.depth.saturating_sub(1),
+            _ => {}
+        }
+        self.last_end = self.cur_token.span.end;
         self.cur_token = self.peek_token.clone();
         self.peek_token = self.l.next_token()
     }
 
-    fn parse_program(&mut self) -> Option<Program> {
+    /// Parses the whole input. Statements that fail to parse are reported
+    /// in `errors` and replaced by [`Statement::Error`], so the program is
+    /// always returned; check `errors` before evaluating it.
+    pub fn parse_program(&mut self) -> Program {
         let mut program = Program { statements: vec![] };
         while self.cur_token.token_kind != TokenKind::Eof {
-            let stmt = self.parse_statement()?;
+            let stmt = self.parse_statement_or_recover();
             program.statements.push(stmt);
+        }
+
+        program
+    }
+
+    // Parses one statement and moves past it. If that fails, tokens are
+    // skipped up to the next `;` or the `}` closing the enclosing block,
+    // and an error node covering them takes the statement's place.
+    fn parse_statement_or_recover(&mut self) -> Statement {
+        let start = self.cur_token.clone();
+        let depth = self.depth;
+
+        if let Some(stmt) = self.parse_statement() {
             self.next_token();
+            return stmt;
+        }
+
+        loop {
+            match self.cur_token.token_kind {
+                TokenKind::Eof => break,
+                TokenKind::RBrace if self.depth == depth && depth > 0 => break,
+                TokenKind::Semicolon | TokenKind::RBrace if self.depth == depth => {
+                    // A stray `}` often ends a statement, as in `fn() { } };`:
+                    // its `;` goes with it rather than failing on its own.
+                    if self.cur_token_is(TokenKind::RBrace)
+                        && self.peek_token_is(TokenKind::Semicolon)
+                    {
+                        self.next_token();
+                    }
+                    self.next_token();
+                    break;
+                }
+                _ => self.next_token(),
+            }
         }
 
-        Some(program)
+        Statement::Error(ErrorStatement {
+            span: Span {
+                end: self.last_end.max(start.span.end),
+                ..start.span
+            },
+            token: start,
+        })
     }
 
-    fn parse_statement(&mut self) -> Option<Box<dyn Statement>> {
+    fn parse_statement(&mut self) -> Option<Statement> {
         match self.cur_token.token_kind {
-            TokenKind::Let => Some(Box::new(self.parse_let_statement()?)),
-            TokenKind::Return => Some(Box::new(self.parse_return_statement()?)),
-            _ => None,
+            TokenKind::Let => Some(Statement::Let(self.parse_let_statement()?)),
+            TokenKind::Return => Some(Statement::Return(self.parse_return_statement()?)),
+            _ => Some(Statement::Expression(self.parse_expression_statement()?)),
         }
     }
 
     fn parse_expression_statement(&mut self) -> Option<ExpressionStatement> {
         let tok = self.cur_token.clone();
 
-        let exp = self.parse_expression(Lowest).unwrap();
+        let exp = self.parse_expression(Lowest)?;
+
+        if self.peek_token_is(TokenKind::Semicolon) {
+            self.next_token();
+        }
 
         let stmt = ExpressionStatement {
             token: tok,
@@ -80,10 +227,363 @@ impl Parser {
         Some(stmt)
     }
 
-    fn parse_expression(&mut self, p: Precedence) -> Option<Box<dyn Expression>> {
+    fn parse_expression(&mut self, precedence: Precedence) -> Option<Expression> {
+        if self.nesting == MAX_NESTING {
+            self.errors.push(
+                Diagnostic::error(
+                    "E0007",
+                    String::from("expression is nested too deeply"),
+                    self.cur_token.span,
+                )
+                .with_note(format!(
+                    "expressions may be nested at most {} levels deep",
+                    MAX_NESTING
+                )),
+            );
+            return None;
+        }
+
+        self.nesting += 1;
+        let exp = self.parse_pratt_expression(precedence);
+        self.nesting -= 1;
+        exp
+    }
+
+    fn parse_pratt_expression(&mut self, precedence: Precedence) -> Option<Expression> {
+        let prefix = match self.prefix_parse_fns.get(&self.cur_token.token_kind) {
+            Some(f) => *f,
+            None => {
+                self.no_prefix_parse_fn_error(self.cur_token.token_kind.clone());
+                return None;
+            }
+        };
+        let mut left_exp = prefix(self)?;
+
+        while !self.peek_token_is(TokenKind::Semicolon) && precedence < self.peek_precedence() {
+            let infix = match self.infix_parse_fns.get(&self.peek_token.token_kind) {
+                Some(f) => *f,
+                None => return Some(left_exp),
+            };
+
+            self.next_token();
+
+            left_exp = infix(self, left_exp)?;
+        }
+
+        Some(left_exp)
+    }
+
+    fn parse_identifier(&mut self) -> Option<Expression> {
+        Some(Expression::Identifier(Identifier {
+            token: self.cur_token.clone(),
+            value: self.cur_token.literal.clone(),
+        }))
+    }
+
+    fn parse_integer_literal(&mut self) -> Option<Expression> {
+        let value = match self.cur_token.literal.parse::<i64>() {
+            Ok(v) => v,
+            Err(_) => {
+                let msg = format!("could not parse {:?} as integer", self.cur_token.literal);
+                self.errors.push(
+                    Diagnostic::error("E0003", msg, self.cur_token.span)
+                        .with_primary_label(String::from("integer literal out of range"))
+                        .with_note(format!(
+                            "integers must be between {} and {}",
+                            i64::MIN,
+                            i64::MAX
+                        )),
+                );
+                return None;
+            }
+        };
+
+        Some(Expression::IntegerLiteral(IntegerLiteral {
+            token: self.cur_token.clone(),
+            value,
+        }))
+    }
+
+    fn parse_string_literal(&mut self) -> Option<Expression> {
+        Some(Expression::StringLiteral(StringLiteral {
+            token: self.cur_token.clone(),
+            value: self.cur_token.literal.clone(),
+        }))
+    }
+
+    fn parse_illegal(&mut self) -> Option<Expression> {
+        // The lexer puts the reason a token is illegal in its literal.
+        let msg = self.cur_token.literal.clone();
+        self.errors
+            .push(Diagnostic::error("E0004", msg, self.cur_token.span));
         None
     }
 
+    fn parse_prefix_expression(&mut self) -> Option<Expression> {
+        let tok = self.cur_token.clone();
+        let operator = self.cur_token.literal.clone();
+
+        self.next_token();
+
+        let right = self.parse_expression(Precedence::Prefix)?;
+
+        Some(Expression::Prefix(PrefixExpression {
+            token: tok,
+            operator,
+            right: Box::new(right),
+        }))
+    }
+
+    fn parse_infix_expression(&mut self, left: Expression) -> Option<Expression> {
+        let tok = self.cur_token.clone();
+        let operator = self.cur_token.literal.clone();
+
+        let precedence = self.cur_precedence();
+        self.next_token();
+        let right = self.parse_expression(precedence)?;
+
+        Some(Expression::Infix(InfixExpression {
+            token: tok,
+            left: Box::new(left),
+            operator,
+            right: Box::new(right),
+        }))
+    }
+
+    fn parse_boolean(&mut self) -> Option<Expression> {
+        Some(Expression::Boolean(Boolean {
+            token: self.cur_token.clone(),
+            value: self.cur_token_is(TokenKind::True),
+        }))
+    }
+
+    fn parse_grouped_expression(&mut self) -> Option<Expression> {
+        self.next_token();
+
+        let exp = self.parse_expression(Lowest)?;
+
+        if !self.expect_peek(TokenKind::RParen) {
+            return None;
+        }
+
+        Some(exp)
+    }
+
+    fn parse_if_expression(&mut self) -> Option<Expression> {
+        let tok = self.cur_token.clone();
+
+        if !self.expect_peek(TokenKind::LParen) {
+            return None;
+        }
+
+        self.next_token();
+        let condition = self.parse_expression(Lowest)?;
+
+        if !self.expect_peek(TokenKind::RParen) {
+            return None;
+        }
+
+        if !self.expect_peek(TokenKind::LBrace) {
+            return None;
+        }
+
+        let consequence = self.parse_block_statement()?;
+
+        let mut alternative = None;
+        if self.peek_token_is(TokenKind::Else) {
+            self.next_token();
+
+            if !self.expect_peek(TokenKind::LBrace) {
+                return None;
+            }
+
+            alternative = Some(self.parse_block_statement()?);
+        }
+
+        Some(Expression::If(IfExpression {
+            token: tok,
+            condition: Box::new(condition),
+            consequence,
+            alternative,
+        }))
+    }
+
+    fn parse_block_statement(&mut self) -> Option<BlockStatement> {
+        let tok = self.cur_token.clone();
+        let mut statements = vec![];
+
+        self.next_token();
+
+        while !self.cur_token_is(TokenKind::RBrace) && !self.cur_token_is(TokenKind::Eof) {
+            let stmt = self.parse_statement_or_recover();
+            statements.push(stmt);
+        }
+
+        if !self.cur_token_is(TokenKind::RBrace) {
+            let msg = format!("expected RBrace, got {:?}", self.cur_token.token_kind);
+            self.errors.push(
+                Diagnostic::error("E0001", msg, self.cur_token.span)
+                    .with_primary_label(String::from("expected RBrace"))
+                    .with_label(tok.span, String::from("unclosed block opened here")),
+            );
+            return None;
+        }
+
+        Some(BlockStatement {
+            token: tok,
+            statements,
+        })
+    }
+
+    fn parse_function_literal(&mut self) -> Option<Expression> {
+        let tok = self.cur_token.clone();
+
+        if !self.expect_peek(TokenKind::LParen) {
+            return None;
+        }
+
+        let parameters = self.parse_function_parameters()?;
+
+        if !self.expect_peek(TokenKind::LBrace) {
+            return None;
+        }
+
+        let body = self.parse_block_statement()?;
+
+        Some(Expression::Function(FunctionLiteral {
+            token: tok,
+            parameters,
+            body,
+        }))
+    }
+
+    fn parse_function_parameters(&mut self) -> Option<Vec<Identifier>> {
+        let mut identifiers = vec![];
+
+        if self.peek_token_is(TokenKind::RParen) {
+            self.next_token();
+            return Some(identifiers);
+        }
+
+        if !self.expect_peek(TokenKind::Ident) {
+            return None;
+        }
+        identifiers.push(Identifier {
+            token: self.cur_token.clone(),
+            value: self.cur_token.literal.clone(),
+        });
+
+        while self.peek_token_is(TokenKind::Comma) {
+            self.next_token();
+            if !self.expect_peek(TokenKind::Ident) {
+                return None;
+            }
+            identifiers.push(Identifier {
+                token: self.cur_token.clone(),
+                value: self.cur_token.literal.clone(),
+            });
+        }
+
+        if !self.expect_peek(TokenKind::RParen) {
+            return None;
+        }
+
+        Some(identifiers)
+    }
+
+    fn parse_call_expression(&mut self, function: Expression) -> Option<Expression> {
+        let tok = self.cur_token.clone();
+        let arguments = self.parse_expression_list(TokenKind::RParen)?;
+
+        Some(Expression::Call(CallExpression {
+            token: tok,
+            function: Box::new(function),
+            arguments,
+        }))
+    }
+
+    fn parse_array_literal(&mut self) -> Option<Expression> {
+        let tok = self.cur_token.clone();
+        let elements = self.parse_expression_list(TokenKind::RBracket)?;
+
+        Some(Expression::Array(ArrayLiteral {
+            token: tok,
+            elements,
+        }))
+    }
+
+    fn parse_hash_literal(&mut self) -> Option<Expression> {
+        let tok = self.cur_token.clone();
+        let mut pairs = vec![];
+
+        while !self.peek_token_is(TokenKind::RBrace) {
+            self.next_token();
+            let key = self.parse_expression(Lowest)?;
+
+            if !self.expect_peek(TokenKind::Colon) {
+                return None;
+            }
+
+            self.next_token();
+            let value = self.parse_expression(Lowest)?;
+
+            pairs.push((key, value));
+
+            if !self.peek_token_is(TokenKind::RBrace) && !self.expect_peek(TokenKind::Comma) {
+                return None;
+            }
+        }
+
+        if !self.expect_peek(TokenKind::RBrace) {
+            return None;
+        }
+
+        Some(Expression::Hash(HashLiteral { token: tok, pairs }))
+    }
+
+    fn parse_index_expression(&mut self, left: Expression) -> Option<Expression> {
+        let tok = self.cur_token.clone();
+
+        self.next_token();
+        let index = self.parse_expression(Lowest)?;
+
+        if !self.expect_peek(TokenKind::RBracket) {
+            return None;
+        }
+
+        Some(Expression::Index(IndexExpression {
+            token: tok,
+            left: Box::new(left),
+            index: Box::new(index),
+        }))
+    }
+
+    /// Parses a comma-separated list of expressions up to and including
+    /// the `end` token, as found in call arguments and array literals.
+    fn parse_expression_list(&mut self, end: TokenKind) -> Option<Vec<Expression>> {
+        let mut list = vec![];
+
+        if self.peek_token_is(end.clone()) {
+            self.next_token();
+            return Some(list);
+        }
+
+        self.next_token();
+        list.push(self.parse_expression(Lowest)?);
+
+        while self.peek_token_is(TokenKind::Comma) {
+            self.next_token();
+            self.next_token();
+            list.push(self.parse_expression(Lowest)?);
+        }
+
+        if !self.expect_peek(end) {
+            return None;
+        }
+
+        Some(list)
+    }
+
     fn parse_let_statement(&mut self) -> Option<LetStatement> {
         let tok = self.cur_token.clone();
 
@@ -100,14 +600,14 @@ impl Parser {
             return None;
         }
 
-        // TODO: We're skipping the expressions until we
-        // encounter a semicolon
-        while !self.cur_token_is(TokenKind::Semicolon) {
+        self.next_token();
+
+        let val = self.parse_expression(Lowest)?;
+
+        if self.peek_token_is(TokenKind::Semicolon) {
             self.next_token();
         }
 
-        let val = self.parse_expression(Lowest).unwrap();
-
         let stmt = LetStatement {
             token: tok,
             name: ident,
@@ -120,21 +620,17 @@ impl Parser {
     fn parse_return_statement(&mut self) -> Option<ReturnStatement> {
         let tok = self.cur_token.clone();
 
-        let ident = Identifier {
-            token: self.cur_token.clone(),
-            value: self.cur_token.literal.clone(),
-        };
-
         self.next_token();
 
-        // TODO: We're skipping the expressions until we // encounter a semicolon
-        while !self.cur_token_is(TokenKind::Semicolon) {
+        let return_value = self.parse_expression(Lowest)?;
+
+        if self.peek_token_is(TokenKind::Semicolon) {
             self.next_token();
         }
 
         let stmt = ReturnStatement {
             token: tok,
-            return_value: Box::new(ident),
+            return_value,
         };
 
         Some(stmt)
@@ -151,25 +647,88 @@ impl Parser {
     fn expect_peek(&mut self, t: TokenKind) -> bool {
         if self.peek_token_is(t.clone()) {
             self.next_token();
-            return true;
+            true
         } else {
-            self.peek_error(t.clone());
-            return false;
+            self.peek_error(t);
+            false
         }
     }
 
+    fn peek_precedence(&self) -> Precedence {
+        precedence_of(&self.peek_token.token_kind)
+    }
+
+    fn cur_precedence(&self) -> Precedence {
+        precedence_of(&self.cur_token.token_kind)
+    }
+
     fn peek_error(&mut self, t: TokenKind) {
-        let msg = format!(
-            "expected next token to be {:?} got {:?} instead",
-            t, self.peek_token.token_kind
+        let msg = format!("expected {:?}, got {:?}", t, self.peek_token.token_kind);
+        self.errors.push(
+            Diagnostic::error("E0001", msg, self.peek_token.span)
+                .with_primary_label(format!("expected {:?}", t)),
+        );
+    }
+
+    fn no_prefix_parse_fn_error(&mut self, t: TokenKind) {
+        let msg = format!("no prefix parse function for {:?} found", t);
+        self.errors.push(
+            Diagnostic::error("E0002", msg, self.cur_token.span)
+                .with_primary_label(String::from("expected an expression")),
         );
-        self.errors.push(msg);
     }
 }
 #[cfg(test)]
 mod tests {
+    use crate::ast::{
+        Expression, ExpressionStatement, Identifier, InfixExpression, IntegerLiteral, LetStatement,
+        Node, Program, Statement,
+    };
     use crate::lexer::Lexer;
-    use crate::parser::Parser;
+    use crate::parser::{Parser, MAX_NESTING};
+    use crate::token::{Span, Token, TokenKind};
+
+    fn parse(input: &str) -> Program {
+        let lexer = Lexer::new(String::from(input));
+        let mut p = Parser::new(lexer);
+        let program = p.parse_program();
+        assert!(p.errors.is_empty(), "parser errors: {:?}", p.errors);
+        program
+    }
+
+    fn parse_expression(input: &str) -> Expression {
+        let lexer = Lexer::new(String::from(input));
+        let mut p = Parser::new(lexer);
+        let exp = p.parse_expression(super::Precedence::Lowest);
+        assert!(p.errors.is_empty(), "parser errors: {:?}", p.errors);
+        exp.unwrap()
+    }
+
+    fn errors(p: &Parser) -> Vec<String> {
+        p.errors.iter().map(|e| e.to_string()).collect()
+    }
+
+    fn token(token_kind: TokenKind, literal: &str) -> Token {
+        Token {
+            token_kind,
+            literal: String::from(literal),
+            span: Span::default(),
+        }
+    }
+
+    fn ident(name: &str) -> Identifier {
+        Identifier {
+            token: token(TokenKind::Ident, name),
+            value: String::from(name),
+        }
+    }
+
+    fn int(value: i64) -> Expression {
+        Expression::IntegerLiteral(IntegerLiteral {
+            token: token(TokenKind::Int, &value.to_string()),
+            value,
+        })
+    }
 
     #[test]
     fn test_let_statements() {
@@ -183,8 +742,528 @@ mod tests {
 
         let lexer = Lexer::new(input);
         let mut p = Parser::new(lexer);
-        let program = p.parse_program().unwrap();
+        let program = p.parse_program();
 
         assert_eq!(program.statements.len(), 3)
     }
+
+    #[test]
+    fn test_let_statement_values() {
+        let program = parse("let x = 5; let y = x;");
+
+        let expected = Program {
+            statements: vec![
+                Statement::Let(LetStatement {
+                    token: token(TokenKind::Let, "let"),
+                    name: ident("x"),
+                    value: int(5),
+                }),
+                Statement::Let(LetStatement {
+                    token: token(TokenKind::Let, "let"),
+                    name: ident("y"),
+                    value: Expression::Identifier(ident("x")),
+                }),
+            ],
+        };
+        assert_eq!(program, expected);
+    }
+
+    #[test]
+    fn test_identifier_expression() {
+        let program = parse("foobar;");
+
+        let expected = Program {
+            statements: vec![Statement::Expression(ExpressionStatement {
+                token: token(TokenKind::Ident, "foobar"),
+                expression: Expression::Identifier(ident("foobar")),
+            })],
+        };
+        assert_eq!(program, expected);
+    }
+
+    #[test]
+    fn test_integer_literal_expression() {
+        assert_eq!(parse_expression("5;"), int(5));
+    }
+
+    #[test]
+    fn test_parsing_prefix_expressions() {
+        let tests = vec![("!5;", "!", 5), ("-15;", "-", 15)];
+
+        for (input, operator, value) in tests {
+            match parse_expression(input) {
+                Expression::Prefix(prefix) => {
+                    assert_eq!(prefix.operator, operator);
+                    assert_eq!(*prefix.right, int(value));
+                }
+                exp => panic!("expected prefix expression, got {:?}", exp),
+            }
+        }
+    }
+
+    #[test]
+    fn test_parsing_infix_expressions() {
+        let tests = vec![
+            ("5 + 5;", TokenKind::Plus, "+"),
+            ("5 - 5;", TokenKind::Minus, "-"),
+            ("5 * 5;", TokenKind::Asterisk, "*"),
+            ("5 / 5;", TokenKind::Slash, "/"),
+            ("5 > 5;", TokenKind::Gt, ">"),
+            ("5 < 5;", TokenKind::Lt, "<"),
+            ("5 == 5;", TokenKind::Eq, "=="),
+            ("5 != 5;", TokenKind::NotEq, "!="),
+        ];
+
+        for (input, token_kind, operator) in tests {
+            let expected = Expression::Infix(InfixExpression {
+                token: token(token_kind, operator),
+                left: Box::new(int(5)),
+                operator: String::from(operator),
+                right: Box::new(int(5)),
+            });
+            assert_eq!(parse_expression(input), expected);
+        }
+
+        match parse_expression("a * b") {
+            Expression::Infix(infix) => {
+                assert_eq!(*infix.left, Expression::Identifier(ident("a")));
+                assert_eq!(*infix.right, Expression::Identifier(ident("b")));
+            }
+            exp => panic!("expected infix expression, got {:?}", exp),
+        }
+    }
+
+    #[test]
+    fn test_operator_precedence_parsing() {
+        let tests = vec![
+            ("-a * b", "((-a) * b)"),
+            ("!-a", "(!(-a))"),
+            ("a + b + c", "((a + b) + c)"),
+            ("a + b - c", "((a + b) - c)"),
+            ("a * b * c", "((a * b) * c)"),
+            ("a * b / c", "((a * b) / c)"),
+            ("a + b / c", "(a + (b / c))"),
+            ("a + b * c + d / e - f", "(((a + (b * c)) + (d / e)) - f)"),
+            ("3 + 4; -5 * 5", "(3 + 4)((-5) * 5)"),
+            ("5 > 4 == 3 < 4", "((5 > 4) == (3 < 4))"),
+            ("5 < 4 != 3 > 4", "((5 < 4) != (3 > 4))"),
+            (
+                "3 + 4 * 5 == 3 * 1 + 4 * 5",
+                "((3 + (4 * 5)) == ((3 * 1) + (4 * 5)))",
+            ),
+            ("true", "true"),
+            ("3 > 5 == false", "((3 > 5) == false)"),
+            ("1 + (2 + 3) + 4", "((1 + (2 + 3)) + 4)"),
+            ("(5 + 5) * 2", "((5 + 5) * 2)"),
+            ("-(5 + 5)", "(-(5 + 5))"),
+            ("!(true == true)", "(!(true == true))"),
+            ("a + add(b * c) + d", "((a + add((b * c))) + d)"),
+            (
+                "add(a, b, 1, 2 * 3, 4 + 5, add(6, 7 * 8))",
+                "add(a, b, 1, (2 * 3), (4 + 5), add(6, (7 * 8)))",
+            ),
+            (
+                "add(a + b + c * d / f + g)",
+                "add((((a + b) + ((c * d) / f)) + g))",
+            ),
+            (
+                "a * [1, 2, 3, 4][b * c] * d",
+                "((a * ([1, 2, 3, 4][(b * c)])) * d)",
+            ),
+            (
+                "add(a * b[2], b[1], 2 * [1, 2][1])",
+                "add((a * (b[2])), (b[1]), (2 * ([1, 2][1])))",
+            ),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(parse(input).string(), expected);
+        }
+    }
+
+    #[test]
+    fn test_grouping_is_structural() {
+        assert_eq!(parse("(1 + 2) * 3;"), parse("((1 + 2) * 3);"));
+        assert_ne!(parse("(1 + 2) * 3;"), parse("(1 + (2 * 3));"));
+    }
+
+    #[test]
+    fn test_string_literal_expression() {
+        match parse_expression(r#""hello\tworld \u{1F600}""#) {
+            Expression::StringLiteral(lit) => assert_eq!(lit.value, "hello\tworld \u{1F600}"),
+            exp => panic!("expected string literal, got {:?}", exp),
+        }
+    }
+
+    #[test]
+    fn test_illegal_token_error() {
+        let lexer = Lexer::new(String::from("let s = \"abc;"));
+        let mut p = Parser::new(lexer);
+
+        p.parse_program();
+        assert_eq!(errors(&p), vec!["1:9: unterminated string literal"]);
+    }
+
+    #[test]
+    fn test_unterminated_block_comment_error() {
+        let lexer = Lexer::new(String::from("let x = 1; /* never\nclosed"));
+        let mut p = Parser::new(lexer);
+        p.parse_program();
+
+        assert_eq!(errors(&p), vec!["1:12: unterminated block comment"]);
+        assert_eq!(p.errors[0].code, "E0004");
+    }
+
+    #[test]
+    fn test_boolean_expression() {
+        for (input, value) in [("true;", true), ("false;", false)] {
+            match parse_expression(input) {
+                Expression::Boolean(b) => assert_eq!(b.value, value),
+                exp => panic!("expected boolean, got {:?}", exp),
+            }
+        }
+    }
+
+    #[test]
+    fn test_if_else_expression() {
+        match parse_expression("if (x < y) { x } else { y }") {
+            Expression::If(if_exp) => {
+                assert_eq!(if_exp.condition.string(), "(x < y)");
+                assert_eq!(if_exp.consequence.statements.len(), 1);
+                assert_eq!(if_exp.alternative.unwrap().statements.len(), 1);
+            }
+            exp => panic!("expected if expression, got {:?}", exp),
+        }
+    }
+
+    #[test]
+    fn test_function_literal_parsing() {
+        match parse_expression("fn(x, y) { x + y; }") {
+            Expression::Function(function) => {
+                assert_eq!(function.parameters, vec![ident("x"), ident("y")]);
+                assert_eq!(function.body.statements.len(), 1);
+                assert_eq!(function.body.statements[0].string(), "(x + y)");
+            }
+            exp => panic!("expected function literal, got {:?}", exp),
+        }
+    }
+
+    #[test]
+    fn test_function_parameter_parsing() {
+        let tests = vec![
+            ("fn() {};", vec![]),
+            ("fn(x) {};", vec![ident("x")]),
+            ("fn(x, y, z) {};", vec![ident("x"), ident("y"), ident("z")]),
+        ];
+
+        for (input, expected) in tests {
+            match parse_expression(input) {
+                Expression::Function(function) => assert_eq!(function.parameters, expected),
+                exp => panic!("expected function literal, got {:?}", exp),
+            }
+        }
+    }
+
+    #[test]
+    fn test_call_expression_parsing() {
+        match parse_expression("add(1, 2 * 3, 4 + 5);") {
+            Expression::Call(call) => {
+                assert_eq!(*call.function, Expression::Identifier(ident("add")));
+                let args: Vec<String> = call.arguments.iter().map(|a| a.string()).collect();
+                assert_eq!(args, vec!["1", "(2 * 3)", "(4 + 5)"]);
+            }
+            exp => panic!("expected call expression, got {:?}", exp),
+        }
+    }
+
+    #[test]
+    fn test_parsing_array_literals() {
+        match parse_expression("[1, 2 * 2, 3 + 3]") {
+            Expression::Array(array) => {
+                let elements: Vec<String> = array.elements.iter().map(|e| e.string()).collect();
+                assert_eq!(elements, vec!["1", "(2 * 2)", "(3 + 3)"]);
+            }
+            exp => panic!("expected array literal, got {:?}", exp),
+        }
+
+        match parse_expression("[]") {
+            Expression::Array(array) => assert!(array.elements.is_empty()),
+            exp => panic!("expected array literal, got {:?}", exp),
+        }
+    }
+
+    #[test]
+    fn test_parsing_index_expressions() {
+        match parse_expression("myArray[1 + 1]") {
+            Expression::Index(index) => {
+                assert_eq!(*index.left, Expression::Identifier(ident("myArray")));
+                assert_eq!(index.index.string(), "(1 + 1)");
+            }
+            exp => panic!("expected index expression, got {:?}", exp),
+        }
+    }
+
+    #[test]
+    fn test_parsing_hash_literals() {
+        let tests = vec![
+            (
+                r#"{"one": 1, "two": 2, "three": 3}"#,
+                r#"{"one": 1, "two": 2, "three": 3}"#,
+            ),
+            ("{}", "{}"),
+            ("{1: 0 + 1, true: 10 - 8}", "{1: (0 + 1), true: (10 - 8)}"),
+            (r#"{"a": {"b": [1]}}"#, r#"{"a": {"b": [1]}}"#),
+        ];
+
+        for (input, expected) in tests {
+            match parse_expression(input) {
+                Expression::Hash(hash) => assert_eq!(hash.string(), expected),
+                exp => panic!("expected hash literal, got {:?}", exp),
+            }
+        }
+    }
+
+    #[test]
+    fn test_parsing_malformed_hash_literals() {
+        let tests = vec![
+            ("{1 2}", "1:4: expected Colon, got Int"),
+            ("{1: 2 3: 4}", "1:7: expected Comma, got Int"),
+        ];
+
+        for (input, msg) in tests {
+            let lexer = Lexer::new(String::from(input));
+            let mut p = Parser::new(lexer);
+
+            p.parse_program();
+            assert_eq!(errors(&p), vec![msg]);
+        }
+    }
+
+    #[test]
+    fn test_string_round_trip() {
+        let tests = vec![
+            "let x = (5 + 5);",
+            "return (-a);",
+            "if ((x < y)) { x; } else { y; }",
+            "let add = fn(a, b) { return (a + b); };",
+            "fn() { }",
+            "add(1, fn(x) { (x * 2); })",
+            r#"let s = "a \"quoted\" \\ line\n";"#,
+            "([1, (2 * 3)][0])",
+            r#"let h = {"k": fn(x) { x; }, 2: [true]};"#,
+        ];
+
+        for input in tests {
+            let printed = parse(input).string();
+            assert_eq!(printed, input);
+            assert_eq!(parse(&printed), parse(input));
+        }
+    }
+
+    #[test]
+    fn test_error_positions() {
+        let input = "let x = 1;\nlet y = 2;\n\nlet 5 = z;";
+        let lexer = Lexer::new(String::from(input));
+        let mut p = Parser::new(lexer);
+
+        p.parse_program();
+        assert_eq!(errors(&p), vec!["4:5: expected Ident, got Int"]);
+    }
+
+    #[test]
+    fn test_spans_reach_ast() {
+        let program = parse("let answer =\n    6 * 7;");
+
+        match &program.statements[0] {
+            Statement::Let(stmt) => {
+                assert_eq!(stmt.token.span.to_string(), "1:1");
+                assert_eq!(stmt.name.token.span.to_string(), "1:5");
+                match &stmt.value {
+                    Expression::Infix(infix) => {
+                        assert_eq!(infix.token.span.to_string(), "2:7");
+                        assert_eq!(infix.token.span.start, 19);
+                        assert_eq!(infix.left.token_literal(), "6");
+                    }
+                    exp => panic!("expected infix expression, got {:?}", exp),
+                }
+            }
+            stmt => panic!("expected let statement, got {:?}", stmt),
+        }
+    }
+
+    #[test]
+    fn test_error_diagnostics() {
+        let tests = vec![
+            ("let = 5;", "E0001"),
+            ("let x = ;", "E0002"),
+            ("99999999999999999999", "E0003"),
+            ("\"abc", "E0004"),
+        ];
+
+        for (input, code) in tests {
+            let lexer = Lexer::new(String::from(input));
+            let mut p = Parser::new(lexer);
+
+            p.parse_program();
+            assert_eq!(p.errors.len(), 1, "{}", input);
+            assert_eq!(p.errors[0].code, code, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_nesting_limit() {
+        let deepest = format!(
+            "{}1{}",
+            "(".repeat(MAX_NESTING - 1),
+            ")".repeat(MAX_NESTING - 1)
+        );
+        let mut p = Parser::new(Lexer::new(deepest));
+        p.parse_program();
+        assert!(p.errors.is_empty(), "{:?}", errors(&p));
+
+        let tests = vec![
+            format!("{}1{};", "(".repeat(MAX_NESTING), ")".repeat(MAX_NESTING)),
+            format!("{}1;", "-".repeat(20000)),
+            format!("{}1{};", "[".repeat(3000), "]".repeat(3000)),
+            format!("{}1{};", "f(".repeat(3000), ")".repeat(3000)),
+        ];
+
+        for input in tests {
+            let mut p = Parser::new(Lexer::new(format!("{}\nlet x = 1;", input)));
+            let program = p.parse_program();
+
+            assert_eq!(p.errors.len(), 1, "{:?}", errors(&p));
+            assert_eq!(p.errors[0].code, "E0007");
+            assert_eq!(program.statements.len(), 2);
+            assert_eq!(program.statements[1].string(), "let x = 1;");
+        }
+    }
+
+    #[test]
+    fn test_unclosed_block_diagnostic() {
+        let input = "if (x) {\n  1;\n";
+        let lexer = Lexer::new(String::from(input));
+        let mut p = Parser::new(lexer);
+
+        p.parse_program();
+        assert_eq!(errors(&p), vec!["3:1: expected RBrace, got Eof"]);
+        assert_eq!(p.errors[0].secondary.len(), 1);
+        assert_eq!(p.errors[0].secondary[0].span.to_string(), "1:8");
+    }
+
+    #[test]
+    fn test_no_prefix_parse_fn_error() {
+        let lexer = Lexer::new(String::from("let x = ;"));
+        let mut p = Parser::new(lexer);
+
+        p.parse_program();
+        assert_eq!(
+            errors(&p),
+            vec!["1:9: no prefix parse function for Semicolon found"]
+        );
+    }
+
+    #[test]
+    fn test_reports_every_error() {
+        let input = "let = 1;\nlet y = 2;\nlet z = ;\nreturn 3 +;\nlet w = 4;";
+        let lexer = Lexer::new(String::from(input));
+        let mut p = Parser::new(lexer);
+        let program = p.parse_program();
+
+        assert_eq!(
+            errors(&p),
+            vec![
+                "1:5: expected Ident, got Assign",
+                "3:9: no prefix parse function for Semicolon found",
+                "4:11: no prefix parse function for Semicolon found",
+            ]
+        );
+        let kinds: Vec<&str> = program
+            .statements
+            .iter()
+            .map(|stmt| match stmt {
+                Statement::Let(_) => "let",
+                Statement::Error(_) => "error",
+                stmt => panic!("unexpected statement {:?}", stmt),
+            })
+            .collect();
+        assert_eq!(kinds, vec!["error", "let", "error", "error", "let"]);
+    }
+
+    #[test]
+    fn test_error_node_spans() {
+        let input = "let x = (1 2);\nlet y = 3;";
+        let lexer = Lexer::new(String::from(input));
+        let mut p = Parser::new(lexer);
+        let program = p.parse_program();
+
+        assert_eq!(errors(&p), vec!["1:12: expected RParen, got Int"]);
+        match &program.statements[0] {
+            Statement::Error(stmt) => {
+                assert_eq!(stmt.token.token_kind, TokenKind::Let);
+                assert_eq!(&input[stmt.span.start..stmt.span.end], "let x = (1 2);");
+            }
+            stmt => panic!("expected error statement, got {:?}", stmt),
+        }
+        assert_eq!(program.statements[1].string(), "let y = 3;");
+    }
+
+    #[test]
+    fn test_recovery_inside_blocks() {
+        let input = "let f = fn(x) {\n  let = x;\n  x + }\n;\nlet g = if (f) { {1: } } else { 2 };\nlet h = 5;";
+        let lexer = Lexer::new(String::from(input));
+        let mut p = Parser::new(lexer);
+        let program = p.parse_program();
+
+        assert_eq!(
+            errors(&p),
+            vec![
+                "2:7: expected Ident, got Assign",
+                "3:7: no prefix parse function for RBrace found",
+                "5:22: no prefix parse function for RBrace found",
+            ]
+        );
+        assert_eq!(program.statements.len(), 3);
+        assert_eq!(
+            program.statements[0].string(),
+            "let f = fn(x) { <error> <error> };"
+        );
+        assert_eq!(
+            program.statements[1].string(),
+            "let g = if (f) { <error> } else { 2; };"
+        );
+        assert_eq!(program.statements[2].string(), "let h = 5;");
+    }
+
+    #[test]
+    fn test_stray_closing_brace() {
+        let lexer = Lexer::new(String::from("let a = 1; } let b = 2;"));
+        let mut p = Parser::new(lexer);
+        let program = p.parse_program();
+
+        assert_eq!(
+            errors(&p),
+            vec!["1:12: no prefix parse function for RBrace found"]
+        );
+        assert_eq!(program.statements.len(), 3);
+        assert_eq!(program.statements[2].string(), "let b = 2;");
+    }
+
+    #[test]
+    fn test_stray_closing_brace_and_semicolon() {
+        let lexer = Lexer::new(String::from("fn() { let = ; } }; 5"));
+        let mut p = Parser::new(lexer);
+        let program = p.parse_program();
+
+        assert_eq!(
+            errors(&p),
+            vec![
+                "1:12: expected Ident, got Assign",
+                "1:18: no prefix parse function for RBrace found",
+            ]
+        );
+        assert_eq!(program.statements.len(), 3);
+        assert_eq!(program.statements[1].string(), "<error>");
+        assert_eq!(program.statements[2].string(), "5");
+    }
 }
@@ -1,23 +1,305 @@
-use std::io;
+use std::time::Instant;
 
-use crate::{lexer::Lexer, token::TokenKind};
+use monkey_rs::{
+    diagnostic::Diagnostic,
+    lexer::Lexer,
+    token::{TokenKind, KEYWORDS},
+    Error, Interpreter, Parser, Program, Value,
+};
 
-pub fn start() {
-    let mut line = String::new();
+use crate::{
+    commands::{print_diagnostics, read_source, token_line},
+    editor::{Line, LineReader},
+    inspect,
+};
+
+const PROMPT: &str = ">> ";
+const CONTINUATION_PROMPT: &str = ".. ";
 
-    io::stdin()
-        .read_line(&mut line)
-        .expect("failed to read line.");
+const HELP: &str = "commands:
+    :tokens [CODE]   print the tokens of CODE, or toggle printing them for every input
+    :ast [CODE]      print the syntax tree of CODE, or toggle printing it
+    :sexpr [CODE]    print CODE as s-expressions, or toggle printing them
+    :time [CODE]     time the evaluation of CODE, or toggle timing every input
+    :env             list the bindings made so far
+    :load FILE       evaluate a script in this session
+    :reset           forget every binding
+    :help            show this help";
 
-    let mut l = Lexer::new(line);
+/// Reads and evaluates input until end of input. Input that leaves a
+/// bracket, string or comment open is continued on the next line. Bindings
+/// persist from one input to the next, and errors are reported without
+/// ending the session. Input starting with `:` is a meta-command; see
+/// [`HELP`].
+///
+/// On a terminal, lines can be edited, earlier input is recalled from a
+/// history kept across sessions, and Tab completes keywords, builtins and
+/// bindings.
+pub fn start() {
+    let mut reader = LineReader::new();
+    let mut session = Session::new();
+    let mut input = String::new();
 
     loop {
-        let tok = l.next_token();
-        if tok.token_kind != TokenKind::Eof {
-            print!("{:?} \n", tok);
-            continue;
+        let prompt = if input.is_empty() {
+            PROMPT
         } else {
+            CONTINUATION_PROMPT
+        };
+        reader.set_completions(session.completions());
+
+        match reader.read_line(prompt) {
+            Ok(Line::Text(line)) => input.push_str(&line),
+            Ok(Line::Interrupted) => {
+                input.clear();
+                continue;
+            }
+            // Ctrl-D: finish the prompt's line before leaving. Unfinished
+            // input is dropped.
+            Ok(Line::Eof) => {
+                println!();
+                break;
+            }
+            Err(err) => {
+                eprintln!("monkey: {}", err);
+                break;
+            }
+        }
+        if input.trim().is_empty() {
+            input.clear();
+            continue;
+        }
+        if is_incomplete(&input) {
+            continue;
+        }
+
+        let line = std::mem::take(&mut input);
+        reader.add_history(&line);
+        match parse_command(&line) {
+            Some((name, argument)) => session.command(name, argument),
+            None => session.eval("<stdin>", &line),
+        }
+    }
+
+    if let Err(err) = reader.save_history() {
+        eprintln!("monkey: cannot save history: {}", err);
+    }
+}
+
+/// Splits a meta-command such as `:load a.mk` into its name and argument,
+/// or returns `None` for ordinary code.
+fn parse_command(input: &str) -> Option<(&str, &str)> {
+    let command = input.trim().strip_prefix(':')?;
+    match command.split_once(char::is_whitespace) {
+        Some((name, argument)) => Some((name, argument.trim())),
+        None => Some((command, "")),
+    }
+}
+
+/// What the REPL keeps between inputs: the interpreter, and which stages
+/// to show for each input.
+struct Session {
+    interpreter: Interpreter,
+    show_tokens: bool,
+    show_ast: bool,
+    show_sexpr: bool,
+    time: bool,
+}
+
+impl Session {
+    fn new() -> Session {
+        Session {
+            interpreter: Interpreter::new(),
+            show_tokens: false,
+            show_ast: false,
+            show_sexpr: false,
+            time: false,
+        }
+    }
+
+    /// The words Tab completes: keywords, builtins and the names bound so
+    /// far.
+    fn completions(&self) -> Vec<String> {
+        let env = self.interpreter.environment();
+        let mut words: Vec<String> = KEYWORDS.iter().map(|(word, _)| word.to_string()).collect();
+        words.extend(env.builtin_names());
+        words.extend(env.bindings().into_iter().map(|(name, _)| name));
+        words.sort();
+        words.dedup();
+        words
+    }
+
+    /// Runs a meta-command. The stage commands run once on their argument,
+    /// or toggle the stage for every input when there is none.
+    fn command(&mut self, name: &str, argument: &str) {
+        match (name, argument) {
+            ("tokens", "") => toggle("tokens", &mut self.show_tokens),
+            ("tokens", code) => print_tokens(code),
+            ("ast", "") => toggle("ast", &mut self.show_ast),
+            ("ast", code) => {
+                print_diagnostics("<stdin>", code, &print_program(code, inspect::tree))
+            }
+            ("sexpr", "") => toggle("sexpr", &mut self.show_sexpr),
+            ("sexpr", code) => {
+                print_diagnostics("<stdin>", code, &print_program(code, inspect::sexpr))
+            }
+            ("time", "") => toggle("time", &mut self.time),
+            ("time", code) => {
+                let time = std::mem::replace(&mut self.time, true);
+                self.eval("<stdin>", code);
+                self.time = time;
+            }
+            ("env", "") => {
+                for (name, value) in self.interpreter.environment().bindings() {
+                    println!("{} = {}", name, value.inspect());
+                }
+            }
+            ("load", "") => eprintln!("usage: :load FILE"),
+            ("load", path) => match read_source(path) {
+                Ok((name, source)) => self.eval(&name, &source),
+                Err(err) => eprintln!("{}: {}", path, err),
+            },
+            ("reset", "") => self.interpreter = Interpreter::new(),
+            ("help", "") => println!("{}", HELP),
+            ("env" | "reset" | "help", _) => eprintln!(":{} takes no argument", name),
+            _ => eprintln!("unknown command :{}; try :help", name),
+        }
+    }
+
+    /// Evaluates `source`, first printing whichever stages are switched on.
+    /// `name` labels its errors.
+    fn eval(&mut self, name: &str, source: &str) {
+        if self.show_tokens {
+            print_tokens(source);
+        }
+        if self.show_ast {
+            print_program(source, inspect::tree);
+        }
+        if self.show_sexpr {
+            print_program(source, inspect::sexpr);
+        }
+
+        let start = Instant::now();
+        let result = self.interpreter.eval_str(source);
+        let elapsed = start.elapsed();
+        match result {
+            // `let` and `puts` evaluate to null; echoing it is just noise.
+            Ok(Value::Null) => {}
+            Ok(value) => println!("{}", value.inspect()),
+            Err(Error::Parse(errors)) => print_diagnostics(name, source, &errors),
+            Err(Error::Runtime(message)) => eprintln!("runtime error: {}", message),
+        }
+        if self.time {
+            println!("time: {:?}", elapsed);
+        }
+    }
+}
+
+fn toggle(stage: &str, on: &mut bool) {
+    *on = !*on;
+    println!("{}: {}", stage, if *on { "on" } else { "off" });
+}
+
+fn print_tokens(source: &str) {
+    let mut lexer = Lexer::new(source.to_string());
+    loop {
+        let tok = lexer.next_token();
+        if tok.token_kind == TokenKind::Eof {
             break;
         }
+        println!("{}", token_line(&tok));
+    }
+}
+
+/// Parses `source` and prints it with `render`, error nodes included.
+/// Returns the syntax errors, which evaluation reports on its own.
+fn print_program(source: &str, render: fn(&Program) -> String) -> Vec<Diagnostic> {
+    let mut p = Parser::new(Lexer::new(source.to_string()));
+    let program = p.parse_program();
+    println!("{}", render(&program));
+    p.errors
+}
+
+/// Reports whether `input` stops inside brackets, a string or a block
+/// comment, so that more lines are needed before it can be evaluated.
+/// Surplus closing brackets do not count; the parser reports those.
+fn is_incomplete(input: &str) -> bool {
+    let mut lexer = Lexer::new(input.to_string());
+    let mut depth = 0;
+
+    loop {
+        let tok = lexer.next_token();
+        match tok.token_kind {
+            TokenKind::LParen | TokenKind::LBrace | TokenKind::LBracket => depth += 1,
+            TokenKind::RParen | TokenKind::RBrace | TokenKind::RBracket => depth -= 1,
+            // Only an unterminated string or comment runs to the end.
+            TokenKind::Illegal
+                if tok.span.end == input.len() && tok.literal.starts_with("unterminated") =>
+            {
+                return true;
+            }
+            TokenKind::Eof => return depth > 0,
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::repl::{is_incomplete, parse_command, Session};
+
+    #[test]
+    fn test_is_incomplete() {
+        let tests = vec![
+            ("let x = 5;", false),
+            ("let add = fn(a, b) {\n", true),
+            ("let add = fn(a, b) {\n  a + b\n};\n", false),
+            ("puts([1,\n", true),
+            ("f(g(1)", true),
+            ("let h = {\"a\": [1, 2]}", false),
+            ("\"abc\ndef", true),
+            ("\"abc\ndef\"", false),
+            ("\"(\"", false),
+            ("1 /* note\n", true),
+            ("1 /* note */ + 2", false),
+            ("1 // (\n", false),
+            ("}}", false),
+            ("\"bad \\q escape\" + (", true),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(is_incomplete(input), expected, "{:?}", input);
+        }
+    }
+
+    #[test]
+    fn test_parse_command() {
+        let tests = vec![
+            (":help\n", Some(("help", ""))),
+            ("  :load  a b.mk \n", Some(("load", "a b.mk"))),
+            (":ast fn(x) {\n  x\n}\n", Some(("ast", "fn(x) {\n  x\n}"))),
+            (":", Some(("", ""))),
+            ("{\"a\": 1}\n", None),
+            ("x\n", None),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(parse_command(input), expected, "{:?}", input);
+        }
+    }
+
+    #[test]
+    fn test_completions() {
+        let mut session = Session::new();
+        session.eval("<stdin>", "let lenient = true; let x = 1;");
+
+        let words = session.completions();
+        for word in ["fn", "return", "len", "puts", "lenient", "x"] {
+            assert!(words.contains(&word.to_string()), "{}", word);
+        }
+        assert!(words.windows(2).all(|pair| pair[0] < pair[1]));
+
+        session.command("reset", "");
+        assert!(!session.completions().contains(&"lenient".to_string()));
     }
 }
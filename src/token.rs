@@ -1,17 +1,49 @@
-#[derive(Debug, PartialEq, Clone)]
+use std::fmt;
+
+#[derive(Debug, Clone)]
 pub struct Token {
     pub token_kind: TokenKind,
     pub literal: String,
+    pub span: Span,
+}
+
+// Tokens compare by kind and literal only, so trees parsed from
+// differently laid out source still compare equal.
+impl PartialEq for Token {
+    fn eq(&self, other: &Token) -> bool {
+        self.token_kind == other.token_kind && self.literal == other.literal
+    }
+}
+
+/// Where a token sits in the source: `start..end` is its byte range, and
+/// `line` and `column` (both 1-based) locate `start` for humans.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+    pub column: usize,
 }
 
-#[derive(Debug, PartialEq, Clone)]
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
 pub enum TokenKind {
     Illegal, // illegal
     Eof,     // eof
 
+    // Trivia, only produced by a lexer asked to keep it
+    Whitespace, // spaces, tabs and newlines
+    Comment,    // // line or /* block */
+
     // Identifiers + literals
-    Ident, // add, foobar, x, y, ...
-    Int,   // 12343456
+    Ident,  // add, foobar, x, y, ...
+    Int,    // 12343456
+    String, // "foobar"
 
     // Operators
     Assign,   // =
@@ -26,11 +58,14 @@ pub enum TokenKind {
     // Delimiters
     Comma,     // ,
     Semicolon, // ;
+    Colon,     // :
 
-    LParen, // (
-    RParen, // )
-    LBrace, // {
-    RBrace, // }
+    LParen,   // (
+    RParen,   // )
+    LBrace,   // {
+    RBrace,   // }
+    LBracket, // [
+    RBracket, // ]
 
     // Keywords
     Function, // fn
@@ -47,15 +82,20 @@ pub enum TokenKind {
     Default,
 }
 
+/// The identifiers reserved as keywords, and the tokens they lex to.
+pub const KEYWORDS: [(&str, TokenKind); 7] = [
+    ("fn", TokenKind::Function),
+    ("let", TokenKind::Let),
+    ("true", TokenKind::True),
+    ("false", TokenKind::False),
+    ("if", TokenKind::If),
+    ("else", TokenKind::Else),
+    ("return", TokenKind::Return),
+];
+
 pub fn lookup_ident(ident: &str) -> TokenKind {
-    match ident {
-        "fn" => TokenKind::Function,
-        "let" => TokenKind::Let,
-        "true" => TokenKind::True,
-        "false" => TokenKind::False,
-        "if" => TokenKind::If,
-        "else" => TokenKind::Else,
-        "return" => TokenKind::Return,
-        _ => TokenKind::Ident,
-    }
+    KEYWORDS
+        .iter()
+        .find(|(keyword, _)| *keyword == ident)
+        .map_or(TokenKind::Ident, |(_, kind)| kind.clone())
 }
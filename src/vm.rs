@@ -0,0 +1,683 @@
+//! A stack machine that runs [`Bytecode`], with the same results as the
+//! [evaluator](crate::evaluator).
+
+use std::{cell::RefCell, collections::BTreeMap, fmt, rc::Rc};
+
+use crate::{
+    builtins::Builtins,
+    code::{read_u16, Opcode},
+    compiler::Bytecode,
+    evaluator::{
+        eval_index_expression, eval_infix_expression, eval_prefix_expression, is_truthy,
+        MAX_CALL_DEPTH,
+    },
+    object::{Closure, CompiledFunction, HashPair, Object, FALSE, NULL, TRUE},
+};
+
+/// How deeply calls may nest before the VM gives up with a stack
+/// overflow error: the top level, and as many calls as the evaluator
+/// allows.
+pub const MAX_FRAMES: usize = MAX_CALL_DEPTH + 1;
+
+/// The locals of one call to a compiled function. Closures created during
+/// the call keep it alive, and see later changes to it, just as the
+/// evaluator's functions share their defining environment.
+pub struct Scope {
+    function: Rc<CompiledFunction>,
+    // A slot is `None` until its `let` has run.
+    slots: RefCell<Vec<Option<Object>>>,
+    parent: Option<Rc<Scope>>,
+}
+
+impl Scope {
+    /// Walks `depth` scopes outwards.
+    fn ancestor(self: &Rc<Scope>, depth: usize) -> &Rc<Scope> {
+        let mut scope = self;
+        for _ in 0..depth {
+            scope = scope.parent.as_ref().expect("scope depth out of range");
+        }
+        scope
+    }
+}
+
+impl PartialEq for Scope {
+    fn eq(&self, other: &Scope) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+impl fmt::Debug for Scope {
+    // Only the names are printed: a closure stored in the scope that
+    // created it would otherwise recurse forever.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Scope")
+            .field("locals", &self.function.locals)
+            .field("parent", &self.parent)
+            .finish()
+    }
+}
+
+struct Frame {
+    function: Rc<CompiledFunction>,
+    // Where to continue once the function this frame calls returns.
+    ip: usize,
+    // `None` for the top level, which has no locals.
+    scope: Option<Rc<Scope>>,
+    // The stack height below the callee, restored when it returns.
+    base: usize,
+}
+
+/// Runs bytecode from a [`Compiler`](crate::compiler::Compiler). Globals
+/// persist from one run to the next, to go with the compiler carrying its
+/// global slots over.
+pub struct Vm {
+    builtins: Builtins,
+    globals: Vec<Option<Object>>,
+    stack: Vec<Object>,
+    frames: Vec<Frame>,
+}
+
+impl Default for Vm {
+    fn default() -> Vm {
+        Vm::new()
+    }
+}
+
+impl Vm {
+    pub fn new() -> Vm {
+        Vm::with_builtins(Builtins::standard())
+    }
+
+    pub fn with_builtins(builtins: Builtins) -> Vm {
+        Vm {
+            builtins,
+            globals: vec![],
+            stack: vec![],
+            frames: vec![],
+        }
+    }
+
+    /// Runs `bytecode`, returning the value of its last statement or the
+    /// message of the runtime error that stopped it.
+    pub fn run(&mut self, bytecode: &Bytecode) -> Result<Object, String> {
+        self.globals.resize(bytecode.globals.len(), None);
+        self.stack.clear();
+        self.frames.clear();
+        self.frames.push(Frame {
+            function: Rc::clone(&bytecode.main),
+            ip: 0,
+            scope: None,
+            base: 0,
+        });
+
+        let result = self.execute(bytecode);
+        self.stack.clear();
+        self.frames.clear();
+        result
+    }
+
+    fn execute(&mut self, bytecode: &Bytecode) -> Result<Object, String> {
+        // The running frame's state is kept in locals and only written
+        // back to its `Frame` across calls.
+        let mut function = Rc::clone(&bytecode.main);
+        let mut scope: Option<Rc<Scope>> = None;
+        let mut ip = 0;
+
+        loop {
+            let ins = &function.instructions;
+            let op = match Opcode::from_byte(ins[ip]) {
+                Some(op) => op,
+                None => return Err(format!("invalid opcode {} at {}", ins[ip], ip)),
+            };
+
+            match op {
+                Opcode::Constant => {
+                    let index = read_u16(&ins[ip + 1..]) as usize;
+                    ip += 3;
+                    self.stack.push(bytecode.constants[index].clone());
+                }
+                Opcode::Pop => {
+                    ip += 1;
+                    self.pop();
+                }
+                Opcode::Add
+                | Opcode::Sub
+                | Opcode::Mul
+                | Opcode::Div
+                | Opcode::Equal
+                | Opcode::NotEqual
+                | Opcode::GreaterThan
+                | Opcode::LessThan => {
+                    ip += 1;
+                    let right = self.pop();
+                    let left = self.stack.last_mut().expect("stack underflow");
+                    // Integers are by far the most common operands, so
+                    // their result replaces the left operand in place.
+                    if let (Object::Integer(l), Object::Integer(r)) = (&*left, &right) {
+                        *left = integer_operation(op, *l, *r)?;
+                    } else {
+                        let left = self.pop();
+                        let result = binary_operation(op, left, right)?;
+                        self.stack.push(result);
+                    }
+                }
+                Opcode::Minus => {
+                    ip += 1;
+                    let result = match self.pop() {
+                        Object::Integer(value) => Object::Integer(value.wrapping_neg()),
+                        right => check(eval_prefix_expression("-", right))?,
+                    };
+                    self.stack.push(result);
+                }
+                Opcode::Bang => {
+                    ip += 1;
+                    let right = self.pop();
+                    self.stack.push(boolean(!is_truthy(&right)));
+                }
+                Opcode::True => {
+                    ip += 1;
+                    self.stack.push(TRUE);
+                }
+                Opcode::False => {
+                    ip += 1;
+                    self.stack.push(FALSE);
+                }
+                Opcode::Null => {
+                    ip += 1;
+                    self.stack.push(NULL);
+                }
+                Opcode::JumpNotTruthy => {
+                    let target = read_u16(&ins[ip + 1..]) as usize;
+                    let condition = self.pop();
+                    ip = if is_truthy(&condition) {
+                        ip + 3
+                    } else {
+                        target
+                    };
+                }
+                Opcode::Jump => {
+                    ip = read_u16(&ins[ip + 1..]) as usize;
+                }
+                Opcode::GetGlobal => {
+                    let slot = read_u16(&ins[ip + 1..]) as usize;
+                    ip += 3;
+                    let value = match &self.globals[slot] {
+                        Some(value) => value.clone(),
+                        None => self.lookup_global(&bytecode.globals[slot])?,
+                    };
+                    self.stack.push(value);
+                }
+                Opcode::SetGlobal => {
+                    let slot = read_u16(&ins[ip + 1..]) as usize;
+                    ip += 3;
+                    self.globals[slot] = Some(self.pop());
+                }
+                Opcode::GetLocal => {
+                    let slot = read_u16(&ins[ip + 1..]) as usize;
+                    ip += 3;
+                    let value = self.load(bytecode, current(&scope), slot)?;
+                    self.stack.push(value);
+                }
+                Opcode::SetLocal => {
+                    let slot = read_u16(&ins[ip + 1..]) as usize;
+                    ip += 3;
+                    let value = self.pop();
+                    current(&scope).slots.borrow_mut()[slot] = Some(value);
+                }
+                Opcode::GetOuter => {
+                    let depth = ins[ip + 1] as usize;
+                    let slot = read_u16(&ins[ip + 2..]) as usize;
+                    ip += 4;
+                    let value = self.load(bytecode, current(&scope).ancestor(depth), slot)?;
+                    self.stack.push(value);
+                }
+                Opcode::Array => {
+                    let count = read_u16(&ins[ip + 1..]) as usize;
+                    ip += 3;
+                    let elements = self.stack.split_off(self.stack.len() - count);
+                    self.stack.push(Object::Array(elements));
+                }
+                Opcode::Hash => {
+                    let count = read_u16(&ins[ip + 1..]) as usize;
+                    ip += 3;
+                    let hash = self.build_hash(count)?;
+                    self.stack.push(hash);
+                }
+                Opcode::HashKey => {
+                    ip += 1;
+                    let key = self.stack.last().expect("stack underflow");
+                    if key.hash_key().is_none() {
+                        return Err(unusable_as_hash_key(key));
+                    }
+                }
+                Opcode::Index => {
+                    ip += 1;
+                    let index = self.pop();
+                    let left = self.pop();
+                    let result = match (&left, &index) {
+                        (Object::Array(elements), Object::Integer(i)) => usize::try_from(*i)
+                            .ok()
+                            .and_then(|i| elements.get(i))
+                            .cloned()
+                            .unwrap_or(NULL),
+                        _ => check(eval_index_expression(left, index))?,
+                    };
+                    self.stack.push(result);
+                }
+                Opcode::Call => {
+                    let count = ins[ip + 1] as usize;
+                    ip += 2;
+                    let callee = self.stack.len() - 1 - count;
+                    match &self.stack[callee] {
+                        Object::Closure(closure) => {
+                            let closure = Rc::clone(closure);
+                            let new_scope = self.enter(&closure, count)?;
+                            self.frames.last_mut().unwrap().ip = ip;
+                            self.frames.push(Frame {
+                                function: Rc::clone(&closure.function),
+                                ip: 0,
+                                scope: Some(Rc::clone(&new_scope)),
+                                base: self.stack.len(),
+                            });
+                            function = Rc::clone(&closure.function);
+                            scope = Some(new_scope);
+                            ip = 0;
+                        }
+                        Object::Builtin(builtin) => {
+                            let builtin = Rc::clone(builtin);
+                            let args = self.stack.split_off(callee + 1);
+                            self.stack.pop();
+                            let result = check(builtin.call(&args))?;
+                            self.stack.push(result);
+                        }
+                        obj => return Err(format!("not a function: {}", obj.type_name())),
+                    }
+                }
+                Opcode::ReturnValue => {
+                    let value = self.pop();
+                    let frame = self.frames.pop().unwrap();
+                    let caller = match self.frames.last() {
+                        Some(caller) => caller,
+                        None => return Ok(value),
+                    };
+                    self.stack.truncate(frame.base);
+                    self.stack.push(value);
+                    function = Rc::clone(&caller.function);
+                    scope = caller.scope.clone();
+                    ip = caller.ip;
+                }
+                Opcode::Closure => {
+                    let index = read_u16(&ins[ip + 1..]) as usize;
+                    ip += 3;
+                    let function = match &bytecode.constants[index] {
+                        Object::CompiledFunction(function) => Rc::clone(function),
+                        obj => return Err(format!("not a function: {}", obj.type_name())),
+                    };
+                    let closure = Closure {
+                        function,
+                        scope: scope.clone(),
+                    };
+                    self.stack.push(Object::Closure(Rc::new(closure)));
+                }
+            }
+        }
+    }
+
+    fn pop(&mut self) -> Object {
+        self.stack.pop().expect("stack underflow")
+    }
+
+    /// Moves the arguments of a call to `closure` off the stack, along
+    /// with the closure itself, into the locals of a new scope.
+    fn enter(&mut self, closure: &Closure, count: usize) -> Result<Rc<Scope>, String> {
+        let function = &closure.function;
+        if count != function.num_parameters {
+            return Err(format!(
+                "wrong number of arguments: want={}, got={}",
+                function.num_parameters, count
+            ));
+        }
+        if self.frames.len() >= MAX_FRAMES {
+            return Err(String::from("stack overflow"));
+        }
+
+        let mut slots: Vec<Option<Object>> = Vec::with_capacity(function.locals.len());
+        slots.extend(self.stack.drain(self.stack.len() - count..).map(Some));
+        slots.resize(function.locals.len(), None);
+        self.stack.pop();
+
+        Ok(Rc::new(Scope {
+            function: Rc::clone(function),
+            slots: RefCell::new(slots),
+            parent: closure.scope.clone(),
+        }))
+    }
+
+    /// Reads a local slot. One whose `let` has not run yet is looked up by
+    /// name in the enclosing scopes instead, as the evaluator would.
+    fn load(&self, bytecode: &Bytecode, scope: &Rc<Scope>, slot: usize) -> Result<Object, String> {
+        if let Some(value) = &scope.slots.borrow()[slot] {
+            return Ok(value.clone());
+        }
+
+        let name = &scope.function.locals[slot];
+        let mut outer = scope.parent.as_ref();
+        while let Some(scope) = outer {
+            if let Some(slot) = scope.function.locals.iter().rposition(|l| l == name) {
+                if let Some(value) = &scope.slots.borrow()[slot] {
+                    return Ok(value.clone());
+                }
+            }
+            outer = scope.parent.as_ref();
+        }
+
+        let global = bytecode.globals.iter().position(|global| global == name);
+        match global.and_then(|slot| self.globals[slot].as_ref()) {
+            Some(value) => Ok(value.clone()),
+            None => self.lookup_global(name),
+        }
+    }
+
+    /// Looks up a name that no global is set for: builtins come last.
+    fn lookup_global(&self, name: &str) -> Result<Object, String> {
+        self.builtins
+            .lookup(name)
+            .ok_or_else(|| format!("identifier not found: {}", name))
+    }
+
+    fn build_hash(&mut self, count: usize) -> Result<Object, String> {
+        let items = self.stack.split_off(self.stack.len() - 2 * count);
+        let mut pairs = BTreeMap::new();
+
+        let mut items = items.into_iter();
+        while let (Some(key), Some(value)) = (items.next(), items.next()) {
+            let hash_key = match key.hash_key() {
+                Some(hash_key) => hash_key,
+                None => return Err(unusable_as_hash_key(&key)),
+            };
+            pairs.insert(hash_key, HashPair { key, value });
+        }
+
+        Ok(Object::Hash(pairs))
+    }
+}
+
+fn current(scope: &Option<Rc<Scope>>) -> &Rc<Scope> {
+    scope.as_ref().expect("local access outside a function")
+}
+
+fn integer_operation(op: Opcode, l: i64, r: i64) -> Result<Object, String> {
+    Ok(match op {
+        Opcode::Add => Object::Integer(l.wrapping_add(r)),
+        Opcode::Sub => Object::Integer(l.wrapping_sub(r)),
+        Opcode::Mul => Object::Integer(l.wrapping_mul(r)),
+        Opcode::Div if r == 0 => return Err(String::from("division by zero")),
+        Opcode::Div => Object::Integer(l.wrapping_div(r)),
+        Opcode::Equal => boolean(l == r),
+        Opcode::NotEqual => boolean(l != r),
+        Opcode::GreaterThan => boolean(l > r),
+        Opcode::LessThan => boolean(l < r),
+        _ => unreachable!("{:?} is not a binary operation", op),
+    })
+}
+
+/// Applies a binary operator to anything but two integers, which
+/// [`integer_operation`] handles.
+fn binary_operation(op: Opcode, left: Object, right: Object) -> Result<Object, String> {
+    let operator = match op {
+        Opcode::Add => "+",
+        Opcode::Sub => "-",
+        Opcode::Mul => "*",
+        Opcode::Div => "/",
+        Opcode::Equal => "==",
+        Opcode::NotEqual => "!=",
+        Opcode::GreaterThan => ">",
+        Opcode::LessThan => "<",
+        _ => unreachable!("{:?} is not a binary operation", op),
+    };
+    check(eval_infix_expression(operator, left, right))
+}
+
+/// Turns the evaluator's error objects into errors.
+fn check(obj: Object) -> Result<Object, String> {
+    match obj {
+        Object::Error(message) => Err(message),
+        obj => Ok(obj),
+    }
+}
+
+fn boolean(value: bool) -> Object {
+    if value {
+        TRUE
+    } else {
+        FALSE
+    }
+}
+
+fn unusable_as_hash_key(key: &Object) -> String {
+    format!("unusable as hash key: {}", key.type_name())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compiler::Compiler;
+    use crate::environment::Environment;
+    use crate::evaluator::eval;
+    use crate::lexer::Lexer;
+    use crate::object::Object;
+    use crate::parser::Parser;
+    use crate::vm::Vm;
+
+    fn run(input: &str) -> Result<Object, String> {
+        let mut p = Parser::new(Lexer::new(input.to_string()));
+        let program = p.parse_program();
+        assert!(p.errors.is_empty(), "parser errors: {:?}", p.errors);
+        let bytecode = Compiler::new().compile(&program).unwrap();
+        Vm::new().run(&bytecode)
+    }
+
+    // Runs `input` on both the VM and the evaluator, which must agree.
+    // Functions differ in representation, so they are compared as printed.
+    fn assert_same_as_evaluator(input: &str) {
+        let mut p = Parser::new(Lexer::new(input.to_string()));
+        let program = p.parse_program();
+        let expected = eval(&program, &mut Environment::new());
+
+        let got = run(input).unwrap_or_else(Object::Error);
+        match (&expected, &got) {
+            (Object::Function(_), Object::Closure(_)) => {
+                assert_eq!(got.inspect(), expected.inspect(), "{}", input)
+            }
+            _ => assert_eq!(got, expected, "{}", input),
+        }
+    }
+
+    #[test]
+    fn test_integer_arithmetic() {
+        let tests = vec![
+            ("1", 1),
+            ("1 + 2", 3),
+            ("1 - 2", -1),
+            ("4 / 2 * 3", 6),
+            ("5 * (2 + 10)", 60),
+            ("-5 + 10", 5),
+            ("-(-5)", 5),
+            ("9223372036854775807 + 1", i64::MIN),
+            ("(0 - 9223372036854775807 - 1) / -1", i64::MIN),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(run(input), Ok(Object::Integer(expected)), "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_runtime_errors() {
+        let tests = vec![
+            ("1 / 0", "division by zero"),
+            ("1 + true", "type mismatch: INTEGER + BOOLEAN"),
+            ("-\"a\"", "unknown operator: -STRING"),
+            ("x", "identifier not found: x"),
+            ("1(2)", "not a function: INTEGER"),
+            ("fn(a) { a }()", "wrong number of arguments: want=1, got=0"),
+            ("len(1)", "argument to `len` not supported, got INTEGER"),
+            ("{[1]: 2}", "unusable as hash key: ARRAY"),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(run(input), Err(String::from(expected)), "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_matches_evaluator() {
+        let tests = vec![
+            // Values of statements.
+            "",
+            "5; let a = 1;",
+            "let a = 1; a",
+            "if (false) { 1 }",
+            "if (1) { } else { 2 }",
+            "if (true) { let x = 1; }",
+            "if (null_check) { 1 }",
+            "return 1; 2",
+            "if (true) { return 3; } 4",
+            "9; if (1 > 2) { 1 } else { return 2; }",
+            // Operators.
+            "1 < 2 == true",
+            "!5",
+            "!!0",
+            "\"a\" + \"b\" == \"ab\"",
+            "\"a\" < \"b\"",
+            "true == 1",
+            "true + true",
+            "[1] == [1]",
+            "{} != {}",
+            "fn() {} == fn() {}",
+            "-true",
+            "!len",
+            // Data structures.
+            "[1, \"two\", [3]]",
+            "[1, 2, 3][1 + 1]",
+            "[1][-1]",
+            "[1][\"a\"]",
+            "{\"one\": 1, 2: \"two\", true: [3]}",
+            "{1: 1, 1: 2}",
+            "{\"a\": 1}[\"b\"]",
+            "{\"a\": 1}[[]]",
+            "1[0]",
+            "{[]: undefined}",
+            "{1: undefined}",
+            // Functions and closures.
+            "fn(x, y) { x + y }",
+            "let f = fn(x) { fn(y) { x * y } }; f(3)",
+            "let f = fn(x) { fn(y) { x * y } }; f(3)(4)",
+            "let f = fn() { 1; }; f()",
+            "let f = fn() { let x = 1; }; f()",
+            "let f = fn() { }; f()",
+            "let f = fn(x) { if (x > 1) { return x; } 0 }; [f(1), f(5)]",
+            "let fib = fn(n) { if (n < 2) { n } else { fib(n - 1) + fib(n - 2) } }; fib(15)",
+            "let apply = fn(f, x) { f(x) }; apply(len, \"four\")",
+            "let len = fn(x) { 42 }; len([1])",
+            "let f = fn() { len([1, 2]) }; let len = fn(x) { 0 }; f()",
+            "let f = fn(a, a) { a }; f(1, 2)",
+            "fn(x) { x }(1, 2)",
+            "let f = fn(x) { y }; f(1)",
+            "let x = 5; let f = fn() { x(1) }; f()",
+            // Late binding and shared scopes.
+            "let f = fn() { x }; let x = 5; f()",
+            "let f = fn() { let g = fn() { x }; let x = 2; g() }; f()",
+            "let x = 1; let f = fn() { let y = x; let x = 2; [y, x] }; f()",
+            "let x = 1; let f = fn(c) { if (c) { let x = 2; } x }; [f(true), f(false)]",
+            "let counter = fn() {
+                let n = 0;
+                let get = fn() { n };
+                let n = 10;
+                get()
+            };
+            counter()",
+            "let outer = fn() {
+                let x = 1;
+                let middle = fn() { let inner = fn() { x }; inner };
+                let i = middle();
+                let x = 2;
+                i()
+            };
+            outer()",
+            "let f = fn() { let v = if (true) { let w = 3; w } else { 0 }; w + v }; f()",
+            "let map = fn(arr, f) {
+                let iter = fn(arr, acc) {
+                    if (len(arr) == 0) { acc } else { iter(rest(arr), push(acc, f(first(arr)))) }
+                };
+                iter(arr, [])
+            };
+            map([1, 2, 3], fn(x) { x * 2 })",
+            "let make = fn() { let self = fn(n) { if (n == 0) { 0 } else { self(n - 1) } }; self }; make()(10)",
+            // A return inside an expression leaves the function (or the
+            // program) straight away.
+            "let f = fn() { let x = if (true) { return 1; }; 2 }; f()",
+            "let f = fn() { [if (true) { return 1; }, 5] }; f()",
+            "let f = fn() { (if (true) { return 1; }) + 1 }; f()",
+            "let x = if (true) { return 1; }; 2",
+            "let f = fn() { -if (true) { return 1; } }; f()",
+            "let f = fn() { {if (true) { return 1; }: 2} }; f()",
+            "let f = fn() { {2: if (true) { return 1; }} }; f()",
+            "let f = fn() { [5][if (true) { return 1; }] }; f()",
+            "let f = fn() { len(if (true) { return 1; }) }; f()",
+            "let g = fn(x) { x }; let f = fn() { g(if (true) { return 1; }) + 2 }; f()",
+            "let f = fn() { if (if (true) { return 1; }) { 2 } }; f()",
+            "let f = fn() { return if (true) { return 1; }; 2 }; [f(), 3]",
+            "let f = fn(c) { let x = if (c) { return 1; } else { 2 }; x * 10 }; [f(true), f(false)]",
+            // Errors stop evaluation wherever they happen.
+            "let f = fn() { 1 + true; 2 }; f(); 3",
+            "[1, x, 3]",
+            "{1: 2, 3: x}",
+            "len(1, 2)",
+            "first(1)",
+            "puts()",
+            // Both engines stop runaway recursion at the same depth.
+            "let f = fn() { f() }; f()",
+            "let f = fn(n) { 1 + f(n + 1) }; f(0); 5",
+            "let f = fn(n) { if (n > 1) { f(n - 1) } else { n } }; f(16384)",
+            "let f = fn(n) { if (n > 1) { f(n - 1) } else { n } }; f(16385)",
+            "let sum = fn(n) { if (n == 0) { 0 } else { n + sum(n - 1) } }; sum(10000)",
+        ];
+
+        // The evaluator needs room on the native stack for deep recursion,
+        // as it has in the `monkey` binary.
+        let deep = std::thread::Builder::new().stack_size(1 << 30);
+        let evaluator = deep.spawn(move || {
+            for input in tests {
+                assert_same_as_evaluator(input);
+            }
+        });
+        evaluator.unwrap().join().unwrap();
+    }
+
+    #[test]
+    fn test_globals_persist() {
+        let mut compiler = Compiler::new();
+        let mut vm = Vm::new();
+        let inputs = vec![
+            ("let a = 2;", Object::Null),
+            ("let double = fn(x) { x * a };", Object::Null),
+            ("double(21)", Object::Integer(42)),
+            (
+                "1 + true",
+                Object::Error(String::from("type mismatch: INTEGER + BOOLEAN")),
+            ),
+            ("let a = 3; double(2)", Object::Integer(6)),
+        ];
+
+        for (input, expected) in inputs {
+            let mut p = Parser::new(Lexer::new(input.to_string()));
+            let bytecode = compiler.compile(&p.parse_program()).unwrap();
+            assert_eq!(
+                vm.run(&bytecode).unwrap_or_else(Object::Error),
+                expected,
+                "{}",
+                input
+            );
+        }
+    }
+}
@@ -0,0 +1,287 @@
+use std::{
+    io::Write,
+    process::{Command, Output, Stdio},
+};
+
+// Runs the `monkey` binary with `input` on standard input.
+fn monkey(args: &[&str], input: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_monkey"))
+        .args(args)
+        .env("NO_COLOR", "1")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(input.as_bytes())
+        .unwrap();
+    child.wait_with_output().unwrap()
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8(output.stdout.clone()).unwrap()
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8(output.stderr.clone()).unwrap()
+}
+
+#[test]
+fn test_run() {
+    let output = monkey(&["run", "-"], "let x = 6; puts(x * 7);");
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "42\n");
+}
+
+#[test]
+fn test_run_syntax_error() {
+    let output = monkey(&["run", "-"], "puts(1);\nlet = 5;");
+    assert_eq!(output.status.code(), Some(65));
+    assert_eq!(stdout(&output), "");
+    assert!(
+        stderr(&output).starts_with("error[E0001]: expected Ident, got Assign\n --> <stdin>:2:5\n"),
+        "{}",
+        stderr(&output)
+    );
+}
+
+#[test]
+fn test_run_runtime_error() {
+    let output = monkey(&["run", "-"], "puts(1); 1 + true; puts(2);");
+    assert_eq!(output.status.code(), Some(70));
+    assert_eq!(stdout(&output), "1\n");
+    assert_eq!(
+        stderr(&output),
+        "<stdin>: runtime error: type mismatch: INTEGER + BOOLEAN\n"
+    );
+
+    let output = monkey(&["run", "-"], "let f = fn() { f() }; f()");
+    assert_eq!(output.status.code(), Some(70));
+    assert_eq!(stderr(&output), "<stdin>: runtime error: stack overflow\n");
+
+    // Recursion well short of the limit still runs.
+    let output = monkey(
+        &["run", "-"],
+        "let sum = fn(n) { if (n == 0) { 0 } else { n + sum(n - 1) } }; puts(sum(4000));",
+    );
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "8002000\n");
+}
+
+#[test]
+fn test_run_vm() {
+    let script = "let fib = fn(n) { if (n < 2) { n } else { fib(n - 1) + fib(n - 2) } };\nputs(fib(15));\nputs(len(\"abc\") + true);";
+    let output = monkey(&["run", "--vm", "-"], script);
+    assert_eq!(output.status.code(), Some(70));
+    assert_eq!(stdout(&output), "610\n");
+    assert_eq!(
+        stderr(&output),
+        "<stdin>: runtime error: type mismatch: INTEGER + BOOLEAN\n"
+    );
+}
+
+#[test]
+fn test_compile() {
+    let dir = std::env::temp_dir();
+    let script = dir.join(format!("monkey-compile-{}.mk", std::process::id()));
+    let compiled = script.with_extension("mkc");
+    std::fs::write(&script, "let x = 6;\nputs(x * 7);\n").unwrap();
+
+    let output = monkey(&["compile", script.to_str().unwrap()], "");
+    assert_eq!(output.status.code(), Some(0), "{}", stderr(&output));
+    let output = monkey(&["run", compiled.to_str().unwrap()], "");
+    assert_eq!(output.status.code(), Some(0), "{}", stderr(&output));
+    assert_eq!(stdout(&output), "42\n");
+
+    // A truncated file is rejected before anything runs.
+    let bytes = std::fs::read(&compiled).unwrap();
+    std::fs::write(&compiled, &bytes[..bytes.len() - 1]).unwrap();
+    let output = monkey(&["run", compiled.to_str().unwrap()], "");
+    assert_eq!(output.status.code(), Some(65));
+    assert!(
+        stderr(&output).contains("invalid bytecode: byte 99: unexpected end of file"),
+        "{}",
+        stderr(&output)
+    );
+
+    std::fs::remove_file(&script).unwrap();
+    std::fs::remove_file(&compiled).unwrap();
+
+    let output = monkey(&["compile", "-"], "1");
+    assert_eq!(output.status.code(), Some(64));
+    let output = monkey(&["compile", "-", "-o", "-"], "let = 1;");
+    assert_eq!(output.status.code(), Some(65));
+}
+
+#[test]
+fn test_run_missing_file() {
+    let output = monkey(&["run", "no/such/file.mk"], "");
+    assert_eq!(output.status.code(), Some(74));
+}
+
+#[test]
+fn test_lex() {
+    let output = monkey(&["lex", "-"], "let x = \"hi\";");
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(
+        stdout(&output),
+        "1:1      Let        \"let\"
+1:5      Ident      \"x\"
+1:7      Assign     \"=\"
+1:9      String     \"hi\"
+1:13     Semicolon  \";\"
+"
+    );
+
+    let output = monkey(&["lex", "-"], "1 @ 2");
+    assert_eq!(output.status.code(), Some(65));
+    assert!(stderr(&output).starts_with("error[E0004]: illegal character '@'"));
+}
+
+#[test]
+fn test_parse() {
+    let output = monkey(&["parse", "-"], "let x = 1 + 2 * 3; -a[0]");
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "let x = (1 + (2 * 3));\n(-(a[0]))\n");
+}
+
+#[test]
+fn test_disasm() {
+    let output = monkey(&["disasm", "-"], "let x = 1;\nputs(x);");
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(
+        stdout(&output),
+        "== main ==
+   1 | let x = 1;
+       0000 OpConstant 0         ; 1
+       0003 OpSetGlobal 0        ; x
+   2 | puts(x);
+       0006 OpGetGlobal 1        ; puts
+       0009 OpGetGlobal 0        ; x
+       0012 OpCall 1
+       0014 OpReturnValue
+
+== constants ==
+0000 INTEGER 1
+"
+    );
+
+    let output = monkey(&["disasm", "-"], "let = 1;");
+    assert_eq!(output.status.code(), Some(65));
+}
+
+#[test]
+fn test_check() {
+    assert_eq!(monkey(&["check", "-"], "1 + true").status.code(), Some(0));
+
+    let output = monkey(&["check", "-"], "let = 1;\nlet y = ;");
+    assert_eq!(output.status.code(), Some(65));
+    assert_eq!(stderr(&output).matches("error[").count(), 2);
+
+    let nested = format!("{}1{}", "(".repeat(3000), ")".repeat(3000));
+    for command in ["check", "run", "fmt"] {
+        let output = monkey(&[command, "-"], &nested);
+        assert_eq!(output.status.code(), Some(65), "{}", command);
+        assert!(stderr(&output).starts_with("error[E0007]"), "{}", command);
+    }
+}
+
+#[test]
+fn test_usage_errors() {
+    assert_eq!(monkey(&["frobnicate"], "").status.code(), Some(64));
+    assert_eq!(monkey(&["run"], "").status.code(), Some(64));
+    assert_eq!(monkey(&["run", "a.mk", "b.mk"], "").status.code(), Some(64));
+    assert_eq!(monkey(&["--help"], "").status.code(), Some(0));
+}
+
+#[test]
+fn test_fmt() {
+    let output = monkey(&["fmt", "-"], "let x=1");
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "let x = 1;\n");
+
+    let output = monkey(&["fmt", "--check", "-"], "let x=1");
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(stdout(&output), "would reformat <stdin>\n");
+
+    assert_eq!(
+        monkey(&["fmt", "--check", "-"], "let x = 1;\n")
+            .status
+            .code(),
+        Some(0)
+    );
+}
+
+#[test]
+fn test_repl() {
+    let output = monkey(
+        &["repl"],
+        "let x = 5;\nx * 2\n\nlet = 1;\n1 + true\nlet f = fn() { f() };\nf()\nputs(\"still here\")\nlet double = fn(n) { n * 2 };\ndouble(x)\n",
+    );
+    assert_eq!(output.status.code(), Some(0));
+
+    let stdout = stdout(&output);
+    let session = stdout.split_once("commands\n").unwrap().1;
+    assert_eq!(
+        session,
+        ">> >> 10\n>> >> >> >> >> >> still here\n>> >> 10\n>> \n"
+    );
+
+    let stderr = stderr(&output);
+    assert!(
+        stderr.starts_with("error[E0001]: expected Ident, got Assign\n --> <stdin>:1:5\n"),
+        "{}",
+        stderr
+    );
+    assert!(stderr.contains("runtime error: type mismatch: INTEGER + BOOLEAN\n"));
+    // Runaway recursion is an error like any other, not the end of the
+    // session.
+    assert!(stderr.ends_with("runtime error: stack overflow\n"));
+}
+
+#[test]
+fn test_repl_multi_line_input() {
+    let output = monkey(
+        &["repl"],
+        "let add = fn(a, b) {\n  a + b\n};\nadd(1,\n2)\n\"two\nlines\"\n",
+    );
+
+    let stdout = stdout(&output);
+    let session = stdout.split_once("commands\n").unwrap().1;
+    assert_eq!(session, ">> .. .. >> .. 3\n>> .. two\nlines\n>> \n");
+    assert_eq!(stderr(&output), "");
+}
+
+#[test]
+fn test_repl_meta_commands() {
+    let script = std::env::temp_dir().join(format!("monkey-load-{}.mk", std::process::id()));
+    std::fs::write(&script, "let twice = fn(f, x) { f(f(x)) };\n").unwrap();
+    let input = format!(
+        ":tokens x + 1\n:sexpr 1 + 2 * 3\n:ast -a\n:load {}\nlet n = 2;\n:env\n\
+         :sexpr\ntwice(fn(x) {{ x * n }},\n3)\n:sexpr\n:reset\n:env\n:nope\n",
+        script.display()
+    );
+    let output = monkey(&["repl"], &input);
+    std::fs::remove_file(&script).unwrap();
+
+    let stdout = stdout(&output);
+    let session = stdout.split_once("commands\n").unwrap().1;
+    assert_eq!(
+        session,
+        ">> 1:1      Ident      \"x\"\n\
+         1:3      Plus       \"+\"\n\
+         1:5      Int        \"1\"\n\
+         >> (+ 1 (* 2 3))\n\
+         >> Program\n  PrefixExpression -\n    Identifier a\n\
+         >> >> >> n = 2\ntwice = fn(f, x) { f(f(x)); }\n\
+         >> sexpr: on\n\
+         >> .. (call twice (fn (params x) (block (* x n))) 3)\n12\n\
+         >> sexpr: off\n\
+         >> >> >> >> \n"
+    );
+    assert_eq!(stderr(&output), "unknown command :nope; try :help\n");
+}